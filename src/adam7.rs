@@ -0,0 +1,153 @@
+use crate::ihdr::Ihdr;
+use crate::pixels;
+
+/// One of the seven reduced images an Adam7-interlaced PNG is split into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pass {
+    pub x_start: u32,
+    pub y_start: u32,
+    pub x_step: u32,
+    pub y_step: u32,
+}
+
+/// The Adam7 passes in the order their scanlines appear in the image data.
+pub const PASSES: [Pass; 7] = [
+    Pass { x_start: 0, y_start: 0, x_step: 8, y_step: 8 },
+    Pass { x_start: 4, y_start: 0, x_step: 8, y_step: 8 },
+    Pass { x_start: 0, y_start: 4, x_step: 4, y_step: 8 },
+    Pass { x_start: 2, y_start: 0, x_step: 4, y_step: 4 },
+    Pass { x_start: 0, y_start: 2, x_step: 2, y_step: 4 },
+    Pass { x_start: 1, y_start: 0, x_step: 2, y_step: 2 },
+    Pass { x_start: 0, y_start: 1, x_step: 1, y_step: 2 },
+];
+
+impl Pass {
+    /// Width and height of this pass for an image of the given size. Either
+    /// can be zero, in which case the pass contributes no scanlines at all.
+    pub fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        (
+            width.saturating_sub(self.x_start).div_ceil(self.x_step),
+            height.saturating_sub(self.y_start).div_ceil(self.y_step),
+        )
+    }
+}
+
+/// Copies the pixels that belong to `pass` out of a full image buffer into a
+/// reduced image of the pass's size.
+pub fn extract_pass(ihdr: &Ihdr, pass: &Pass, image: &[u8]) -> Vec<u8> {
+    let (pass_width, pass_height) = pass.size(ihdr.width, ihdr.height);
+    let image_stride = pixels::stride(ihdr, ihdr.width);
+    let pass_stride = pixels::stride(ihdr, pass_width);
+    let bits = pixel_bits(ihdr);
+
+    let mut reduced = vec![0; pass_stride * pass_height as usize];
+    for py in 0..pass_height as usize {
+        let y = pass.y_start as usize + py * pass.y_step as usize;
+        let src_row = &image[y * image_stride..(y + 1) * image_stride];
+        let dst_row = &mut reduced[py * pass_stride..(py + 1) * pass_stride];
+
+        for px in 0..pass_width as usize {
+            let x = pass.x_start as usize + px * pass.x_step as usize;
+            copy_pixel(src_row, x, dst_row, px, bits);
+        }
+    }
+    reduced
+}
+
+/// Copies the pixels of a reduced pass image back to their place in a full
+/// image buffer.
+pub fn merge_pass(ihdr: &Ihdr, pass: &Pass, reduced: &[u8], image: &mut [u8]) {
+    let (pass_width, pass_height) = pass.size(ihdr.width, ihdr.height);
+    let image_stride = pixels::stride(ihdr, ihdr.width);
+    let pass_stride = pixels::stride(ihdr, pass_width);
+    let bits = pixel_bits(ihdr);
+
+    for py in 0..pass_height as usize {
+        let y = pass.y_start as usize + py * pass.y_step as usize;
+        let src_row = &reduced[py * pass_stride..(py + 1) * pass_stride];
+        let dst_row = &mut image[y * image_stride..(y + 1) * image_stride];
+
+        for px in 0..pass_width as usize {
+            let x = pass.x_start as usize + px * pass.x_step as usize;
+            copy_pixel(src_row, px, dst_row, x, bits);
+        }
+    }
+}
+
+fn pixel_bits(ihdr: &Ihdr) -> usize {
+    ihdr.color_type.channels() * ihdr.bit_depth as usize
+}
+
+/// Copies pixel number `src_x` of `src` to pixel number `dst_x` of `dst`.
+/// Pixels narrower than a byte are packed from the most significant bit,
+/// and only ever come in 1, 2 or 4 bit sizes so they never straddle bytes.
+fn copy_pixel(src: &[u8], src_x: usize, dst: &mut [u8], dst_x: usize, bits: usize) {
+    if bits >= 8 {
+        let bytes = bits / 8;
+        dst[dst_x * bytes..(dst_x + 1) * bytes].copy_from_slice(&src[src_x * bytes..(src_x + 1) * bytes]);
+        return;
+    }
+
+    let mask = (1u8 << bits) - 1;
+    let src_shift = 8 - bits - (src_x * bits) % 8;
+    let dst_shift = 8 - bits - (dst_x * bits) % 8;
+
+    let value = (src[src_x * bits / 8] >> src_shift) & mask;
+    let byte = &mut dst[dst_x * bits / 8];
+    *byte = (*byte & !(mask << dst_shift)) | (value << dst_shift);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::{ColorType, InterlaceMethod};
+
+    fn testing_ihdr(width: u32, height: u32, color_type: ColorType, bit_depth: u8) -> Ihdr {
+        Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::Adam7,
+        }
+    }
+
+    #[test]
+    fn test_pass_sizes() {
+        let sizes: Vec<(u32, u32)> = PASSES.iter().map(|pass| pass.size(8, 8)).collect();
+        assert_eq!(sizes, [(1, 1), (1, 1), (2, 1), (2, 2), (4, 2), (4, 4), (8, 4)]);
+
+        let sizes: Vec<(u32, u32)> = PASSES.iter().map(|pass| pass.size(1, 1)).collect();
+        assert_eq!(sizes, [(1, 1), (0, 1), (1, 0), (0, 1), (1, 0), (0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn test_passes_cover_every_pixel_once() {
+        let ihdr = testing_ihdr(13, 11, ColorType::Grayscale, 8);
+        let image: Vec<u8> = (0..13 * 11).map(|i| i as u8).collect();
+
+        let mut merged = vec![0; image.len()];
+        let mut total = 0;
+        for pass in &PASSES {
+            let reduced = extract_pass(&ihdr, pass, &image);
+            total += reduced.len();
+            merge_pass(&ihdr, pass, &reduced, &mut merged);
+        }
+
+        assert_eq!(total, image.len());
+        assert_eq!(merged, image);
+    }
+
+    #[test]
+    fn test_extract_sub_byte_pixels() {
+        // One row of 2 bit pixels 0, 1, 2, 3, 3, 2, 1, 0
+        let ihdr = testing_ihdr(8, 1, ColorType::Grayscale, 2);
+        let image = [0b0001_1011, 0b1110_0100];
+
+        assert_eq!(extract_pass(&ihdr, &PASSES[1], &image), [0b1100_0000]);
+        assert_eq!(extract_pass(&ihdr, &PASSES[3], &image), [0b1001_0000]);
+        assert_eq!(extract_pass(&ihdr, &PASSES[5], &image), [0b0111_1000]);
+    }
+}
@@ -0,0 +1,575 @@
+use std::fmt::Display;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::{Ihdr, IhdrError};
+use crate::png::Png;
+
+/// Length of an acTL chunk's data.
+pub const ACTL_LENGTH: usize = 8;
+/// Length of an fcTL chunk's data.
+pub const FCTL_LENGTH: usize = 26;
+
+/// The contents of an acTL chunk, which marks a PNG as animated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    /// How often to play the animation, 0 meaning forever.
+    pub num_plays: u32,
+}
+
+/// The contents of an fcTL chunk, which starts a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    /// The frame delay is `delay_num / delay_den` seconds, where a zero
+    /// denominator means 1/100.
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+/// The parsed header of an fdAT chunk. The rest of the chunk is image data
+/// in the same format as IDAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameData<'a> {
+    pub sequence_number: u32,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    None = 0,
+    Background = 1,
+    Previous = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    Source = 0,
+    Over = 1,
+}
+
+/// One frame of an animation with its compressed image data, taken either
+/// from IDAT chunks or from the data part of fdAT chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub control: FrameControl,
+    pub data: Vec<&'a [u8]>,
+    /// Whether the frame is the static image stored in IDAT.
+    pub is_default_image: bool,
+}
+
+impl AnimationControl {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(ACTL_LENGTH);
+        data.extend_from_slice(&self.num_frames.to_be_bytes());
+        data.extend_from_slice(&self.num_plays.to_be_bytes());
+        Chunk::new(ChunkType::ACTL, data)
+    }
+}
+
+impl TryFrom<&[u8]> for AnimationControl {
+    type Error = ApngError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != ACTL_LENGTH {
+            return Err(ApngError::InvalidLength { chunk_type: ChunkType::ACTL, length: data.len() });
+        }
+
+        Ok(AnimationControl { num_frames: be_u32(&data[0..4]), num_plays: be_u32(&data[4..8]) })
+    }
+}
+
+impl FrameControl {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(FCTL_LENGTH);
+        data.extend_from_slice(&self.sequence_number.to_be_bytes());
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.extend_from_slice(&self.x_offset.to_be_bytes());
+        data.extend_from_slice(&self.y_offset.to_be_bytes());
+        data.extend_from_slice(&self.delay_num.to_be_bytes());
+        data.extend_from_slice(&self.delay_den.to_be_bytes());
+        data.push(self.dispose_op as u8);
+        data.push(self.blend_op as u8);
+        Chunk::new(ChunkType::FCTL, data)
+    }
+}
+
+impl TryFrom<&[u8]> for FrameControl {
+    type Error = ApngError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != FCTL_LENGTH {
+            return Err(ApngError::InvalidLength { chunk_type: ChunkType::FCTL, length: data.len() });
+        }
+
+        Ok(FrameControl {
+            sequence_number: be_u32(&data[0..4]),
+            width: be_u32(&data[4..8]),
+            height: be_u32(&data[8..12]),
+            x_offset: be_u32(&data[12..16]),
+            y_offset: be_u32(&data[16..20]),
+            delay_num: u16::from_be_bytes([data[20], data[21]]),
+            delay_den: u16::from_be_bytes([data[22], data[23]]),
+            dispose_op: DisposeOp::try_from(data[24])?,
+            blend_op: BlendOp::try_from(data[25])?,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for FrameData<'a> {
+    type Error = ApngError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let (sequence_number, data) = data
+            .split_first_chunk::<4>()
+            .ok_or(ApngError::InvalidLength { chunk_type: ChunkType::FDAT, length: data.len() })?;
+
+        Ok(FrameData { sequence_number: u32::from_be_bytes(*sequence_number), data })
+    }
+}
+
+impl TryFrom<u8> for DisposeOp {
+    type Error = ApngError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(DisposeOp::None),
+            1 => Ok(DisposeOp::Background),
+            2 => Ok(DisposeOp::Previous),
+            _ => Err(ApngError::InvalidDisposeOp(value)),
+        }
+    }
+}
+
+impl TryFrom<u8> for BlendOp {
+    type Error = ApngError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(BlendOp::Source),
+            1 => Ok(BlendOp::Over),
+            _ => Err(ApngError::InvalidBlendOp(value)),
+        }
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Whether chunks of this type belong to an animation's frames.
+pub fn is_frame_chunk(chunk_type: &ChunkType) -> bool {
+    *chunk_type == ChunkType::FCTL || *chunk_type == ChunkType::FDAT
+}
+
+/// The sequence number of an fcTL or fdAT chunk, `None` for other chunks.
+pub fn sequence_number(chunk: &Chunk) -> Option<u32> {
+    if !is_frame_chunk(chunk.chunk_type()) {
+        return None;
+    }
+    chunk.data().first_chunk::<4>().map(|bytes| u32::from_be_bytes(*bytes))
+}
+
+/// Whether putting a chunk that isn't part of a frame at `index` would
+/// separate a frame's fcTL from its data or split a run of fdAT chunks.
+pub fn splits_frame(chunk_types: &[&ChunkType], index: usize) -> bool {
+    let before = index.checked_sub(1).and_then(|i| chunk_types.get(i));
+    let after = chunk_types.get(index);
+
+    match (before, after) {
+        (Some(&before), _) if *before == ChunkType::FCTL => true,
+        (Some(&before), Some(&after)) => *before == ChunkType::FDAT && *after == ChunkType::FDAT,
+        _ => false,
+    }
+}
+
+/// Iterates over the frames of an animated PNG in file order.
+pub struct Frames<'a> {
+    chunks: &'a [Chunk],
+    position: usize,
+}
+
+impl<'a> Frames<'a> {
+    pub fn new(chunks: &'a [Chunk]) -> Frames<'a> {
+        Frames { chunks, position: 0 }
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Result<Frame<'a>, ApngError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.position + self.chunks[self.position..].iter().position(|c| *c.chunk_type() == ChunkType::FCTL)?;
+        let control = match FrameControl::try_from(self.chunks[start].data()) {
+            Ok(control) => control,
+            Err(err) => {
+                self.position = self.chunks.len();
+                return Some(Err(err));
+            }
+        };
+
+        let mut data = Vec::new();
+        let mut is_default_image = false;
+        self.position = start + 1;
+
+        while let Some(chunk) = self.chunks.get(self.position) {
+            match *chunk.chunk_type() {
+                ChunkType::FCTL | ChunkType::IEND => break,
+                ChunkType::IDAT => {
+                    is_default_image = true;
+                    data.push(chunk.data());
+                }
+                ChunkType::FDAT => match FrameData::try_from(chunk.data()) {
+                    Ok(frame_data) => data.push(frame_data.data),
+                    Err(err) => {
+                        self.position = self.chunks.len();
+                        return Some(Err(err));
+                    }
+                },
+                _ => {}
+            }
+            self.position += 1;
+        }
+
+        if data.is_empty() {
+            return Some(Err(ApngError::MissingFrameData(control.sequence_number)));
+        }
+        Some(Ok(Frame { control, data, is_default_image }))
+    }
+}
+
+/// Checks an animated PNG against the APNG rules: acTL comes before the image
+/// data, fcTL and fdAT sequence numbers count up from zero in file order,
+/// every frame has data and fits in the image, and acTL declares the right
+/// number of frames. A PNG without an acTL chunk is valid as long as it has
+/// no frame chunks either.
+pub fn validate(png: &Png) -> Result<(), ApngError> {
+    let chunks = png.chunks();
+    let first_idat = chunks.iter().position(|c| *c.chunk_type() == ChunkType::IDAT);
+
+    let Some(actl_index) = chunks.iter().position(|c| *c.chunk_type() == ChunkType::ACTL) else {
+        if chunks.iter().any(|c| is_frame_chunk(c.chunk_type())) {
+            return Err(ApngError::MissingAnimationControl);
+        }
+        return Ok(());
+    };
+    if first_idat.is_some_and(|idat| idat < actl_index) {
+        return Err(ApngError::AnimationControlAfterImageData);
+    }
+    let control = AnimationControl::try_from(chunks[actl_index].data())?;
+
+    let frame_chunks = chunks.iter().filter(|c| is_frame_chunk(c.chunk_type()));
+    for (expected, chunk) in (0..).zip(frame_chunks) {
+        let actual = sequence_number(chunk)
+            .ok_or(ApngError::InvalidLength { chunk_type: chunk.chunk_type().clone(), length: chunk.length() })?;
+        if actual != expected {
+            return Err(ApngError::SequenceNumber { expected, actual });
+        }
+    }
+
+    let ihdr = chunks.first().filter(|c| *c.chunk_type() == ChunkType::IHDR).ok_or(ApngError::MissingImageHeader)?;
+    let ihdr = Ihdr::try_from(ihdr.data())?;
+    let mut count = 0;
+    for frame in png.frames() {
+        let frame = frame?.control;
+        let fits = frame.width > 0
+            && frame.height > 0
+            && frame.x_offset.checked_add(frame.width).is_some_and(|right| right <= ihdr.width)
+            && frame.y_offset.checked_add(frame.height).is_some_and(|bottom| bottom <= ihdr.height);
+        let is_first = count == 0 && first_idat.is_some_and(|idat| chunks[..idat].iter().any(|c| *c.chunk_type() == ChunkType::FCTL));
+        if !fits || (is_first && (frame.x_offset, frame.y_offset, frame.width, frame.height) != (0, 0, ihdr.width, ihdr.height)) {
+            return Err(ApngError::FrameOutOfBounds(frame.sequence_number));
+        }
+        count += 1;
+    }
+
+    if control.num_frames == 0 || control.num_frames != count {
+        return Err(ApngError::FrameCount { declared: control.num_frames, actual: count });
+    }
+    Ok(())
+}
+
+/// Rewrites the sequence numbers of every fcTL and fdAT chunk so they count
+/// up from zero in file order, as needed after frames were moved, added or
+/// removed.
+pub fn renumber(png: &mut Png) {
+    let mut next = 0u32;
+    png.map_chunk_data(|chunk| {
+        if !is_frame_chunk(chunk.chunk_type()) || chunk.length() < 4 {
+            return None;
+        }
+
+        let mut data = chunk.data().to_vec();
+        data[..4].copy_from_slice(&next.to_be_bytes());
+        next += 1;
+        Some(data)
+    });
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ApngError {
+    InvalidLength { chunk_type: ChunkType, length: usize },
+    InvalidDisposeOp(u8),
+    InvalidBlendOp(u8),
+    MissingAnimationControl,
+    AnimationControlAfterImageData,
+    SequenceNumber { expected: u32, actual: u32 },
+    /// The frame with this sequence number has neither IDAT nor fdAT data.
+    MissingFrameData(u32),
+    FrameOutOfBounds(u32),
+    FrameCount { declared: u32, actual: u32 },
+    MissingImageHeader,
+    InvalidImageHeader(IhdrError),
+}
+
+impl std::error::Error for ApngError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApngError::InvalidImageHeader(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ApngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApngError::InvalidLength { chunk_type, length } => {
+                write!(f, "{} chunk has an invalid length of {} bytes", chunk_type, length)
+            }
+            ApngError::InvalidDisposeOp(op) => write!(f, "Invalid frame dispose operation {}", op),
+            ApngError::InvalidBlendOp(op) => write!(f, "Invalid frame blend operation {}", op),
+            ApngError::MissingAnimationControl => write!(f, "The file has frame chunks but no acTL chunk"),
+            ApngError::AnimationControlAfterImageData => write!(f, "The acTL chunk must come before the image data"),
+            ApngError::SequenceNumber { expected, actual } => {
+                write!(f, "Expected frame sequence number {} but found {}", expected, actual)
+            }
+            ApngError::MissingFrameData(sequence_number) => {
+                write!(f, "The frame starting at sequence number {} has no image data", sequence_number)
+            }
+            ApngError::FrameOutOfBounds(sequence_number) => write!(
+                f,
+                "The frame starting at sequence number {} does not fit the image",
+                sequence_number
+            ),
+            ApngError::FrameCount { declared, actual } => {
+                write!(f, "The acTL chunk declares {} frames but the file has {}", declared, actual)
+            }
+            ApngError::MissingImageHeader => write!(f, "The file doesn't start with an IHDR chunk"),
+            ApngError::InvalidImageHeader(err) => write!(f, "Invalid IHDR chunk: {}", err),
+        }
+    }
+}
+
+impl From<IhdrError> for ApngError {
+    fn from(err: IhdrError) -> Self {
+        ApngError::InvalidImageHeader(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use crate::structure::StructureError;
+    use crate::ihdr::{ColorType, Ihdr, InterlaceMethod};
+    use crate::pixels;
+
+    fn frame_control(sequence_number: u32, width: u32, height: u32) -> FrameControl {
+        FrameControl {
+            sequence_number,
+            width,
+            height,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 1,
+            delay_den: 10,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        }
+    }
+
+    fn frame_data(sequence_number: u32, data: &[u8]) -> Chunk {
+        let mut bytes = sequence_number.to_be_bytes().to_vec();
+        bytes.extend_from_slice(data);
+        Chunk::new(ChunkType::FDAT, bytes)
+    }
+
+    /// A 4x4 animation of three frames: the default image, then one frame
+    /// split over two fdAT chunks and one in a single fdAT chunk.
+    fn testing_apng() -> Png {
+        let ihdr = Ihdr {
+            width: 4,
+            height: 4,
+            bit_depth: 8,
+            color_type: ColorType::Grayscale,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        };
+        let image = pixels::encode(&ihdr, &[128; 16]).unwrap();
+        let (first, second) = image.split_at(4);
+
+        Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            AnimationControl { num_frames: 3, num_plays: 0 }.to_chunk(),
+            frame_control(0, 4, 4).to_chunk(),
+            Chunk::new(ChunkType::IDAT, image.clone()),
+            frame_control(1, 4, 4).to_chunk(),
+            frame_data(2, first),
+            frame_data(3, second),
+            frame_control(4, 2, 2).to_chunk(),
+            frame_data(5, &image),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ])
+    }
+
+    fn sequence_numbers(png: &Png) -> Vec<u32> {
+        png.chunks().iter().filter_map(sequence_number).collect()
+    }
+
+    #[test]
+    fn test_control_round_trips() {
+        let actl = AnimationControl { num_frames: 3, num_plays: 2 };
+        assert_eq!(AnimationControl::try_from(actl.to_chunk().data()).unwrap(), actl);
+
+        let mut fctl = frame_control(7, 3, 2);
+        fctl.dispose_op = DisposeOp::Previous;
+        fctl.blend_op = BlendOp::Over;
+        assert_eq!(FrameControl::try_from(fctl.to_chunk().data()).unwrap(), fctl);
+    }
+
+    #[test]
+    fn test_invalid_controls() {
+        assert_eq!(
+            AnimationControl::try_from([0; 7].as_slice()),
+            Err(ApngError::InvalidLength { chunk_type: ChunkType::ACTL, length: 7 })
+        );
+
+        let mut data = frame_control(0, 1, 1).to_chunk().data().to_vec();
+        data[24] = 3;
+        assert_eq!(FrameControl::try_from(data.as_slice()), Err(ApngError::InvalidDisposeOp(3)));
+    }
+
+    #[test]
+    fn test_frames() {
+        let png = testing_apng();
+        let frames: Vec<Frame> = png.frames().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(frames.len(), 3);
+        assert!(frames[0].is_default_image);
+        assert_eq!(frames[1].data.len(), 2);
+        assert_eq!(frames[1].data.concat(), frames[0].data.concat());
+        assert!(!frames[2].is_default_image);
+        assert_eq!((frames[2].control.width, frames[2].control.height), (2, 2));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&testing_apng()).is_ok());
+
+        let mut png = testing_apng();
+        png.replace_chunk("acTL", AnimationControl { num_frames: 2, num_plays: 0 }.to_chunk()).unwrap();
+        assert!(matches!(validate(&png), Err(ApngError::FrameCount { declared: 2, actual: 3 })));
+
+        let mut png = testing_apng();
+        png.remove_chunk("acTL").unwrap();
+        assert!(matches!(validate(&png), Err(ApngError::MissingAnimationControl)));
+        assert!(matches!(png.validate(), Err(crate::Error::Apng(ApngError::MissingAnimationControl))));
+    }
+
+    #[test]
+    fn test_frame_out_of_bounds() {
+        let mut png = testing_apng();
+        let mut frame = frame_control(4, 2, 2);
+        frame.x_offset = 3;
+        png.map_chunk_data(|chunk| match sequence_number(chunk) {
+            Some(4) => Some(frame.to_chunk().data().to_vec()),
+            _ => None,
+        });
+
+        assert!(matches!(validate(&png), Err(ApngError::FrameOutOfBounds(4))));
+    }
+
+    #[test]
+    fn test_reordered_frames_are_detected_and_renumbered() {
+        // move the last frame in front of the second one
+        let mut chunks: Vec<Chunk> = testing_apng()
+            .chunks()
+            .iter()
+            .map(|c| Chunk::new(c.chunk_type().clone(), c.data().to_vec()))
+            .collect();
+        let last_frame: Vec<Chunk> = chunks.drain(7..9).collect();
+        chunks.splice(4..4, last_frame);
+        let mut png = Png::from_chunks(chunks);
+
+        assert_eq!(sequence_numbers(&png), [0, 4, 5, 1, 2, 3]);
+        assert!(matches!(validate(&png), Err(ApngError::SequenceNumber { expected: 1, actual: 4 })));
+
+        renumber(&mut png);
+        assert_eq!(sequence_numbers(&png), [0, 1, 2, 3, 4, 5]);
+        assert!(validate(&png).is_ok());
+
+        let widths: Vec<u32> = png.frames().map(|frame| frame.unwrap().control.width).collect();
+        assert_eq!(widths, [4, 2, 4]);
+    }
+
+    #[test]
+    fn test_renumber_after_removing_a_frame() {
+        let mut png = testing_apng();
+        png.retain(|chunk| !matches!(sequence_number(chunk), Some(1..=3))).unwrap();
+        png.replace_chunk("acTL", AnimationControl { num_frames: 2, num_plays: 0 }.to_chunk()).unwrap();
+        assert!(matches!(validate(&png), Err(ApngError::SequenceNumber { expected: 1, actual: 4 })));
+
+        renumber(&mut png);
+        assert_eq!(sequence_numbers(&png), [0, 1, 2]);
+        assert!(validate(&png).is_ok());
+    }
+
+    #[test]
+    fn test_messages_keep_frames_intact() {
+        let mut png = testing_apng();
+        let message = || Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"message".to_vec());
+
+        // right after an fcTL and between two fdAT chunks of the same frame
+        assert!(png.insert_chunk_at(3, message()).is_err());
+        assert!(png.insert_chunk_at(6, message()).is_err());
+        assert!(png.insert_after("fcTL", message()).is_err());
+
+        // between frames and at the end are fine
+        png.insert_chunk_at(7, message()).unwrap();
+        png.append_chunk(message()).unwrap();
+        assert!(validate(&png).is_ok());
+        assert_eq!(png.frames().count(), 3);
+    }
+
+    #[test]
+    fn test_frame_chunks_are_only_replaced_by_frame_chunks() {
+        let mut png = testing_apng();
+        let message = || Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"message".to_vec());
+
+        let result = png.replace_chunk("fcTL", message());
+        assert!(matches!(result, Err(crate::Error::Structure(StructureError::SplitsFrame { index: 2 }))));
+        let result = png.replace_chunk("fdAT", message());
+        assert!(matches!(result, Err(crate::Error::Structure(StructureError::SplitsFrame { index: 5 }))));
+
+        png.replace_chunk("fdAT", frame_data(2, &[0; 4])).unwrap();
+        assert_eq!(png.frames().count(), 3);
+    }
+
+    #[test]
+    fn test_not_animated() {
+        let mut png = testing_apng();
+        png.retain(|chunk| !is_frame_chunk(chunk.chunk_type()) && *chunk.chunk_type() != ChunkType::ACTL).unwrap();
+
+        assert!(validate(&png).is_ok());
+        assert_eq!(png.frames().count(), 0);
+    }
+}
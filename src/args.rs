@@ -0,0 +1,388 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use png_msg::chunk_type::ChunkType;
+use png_msg::compression::Codec;
+use png_msg::ihdr::ColorType;
+use png_msg::lsb::{Channel, LsbOptions};
+use png_msg::text;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "png_msg",
+    version,
+    about = "Hide secret messages inside PNG files",
+    after_help = "Exit codes:\n  0  success\n  1  other error\n  2  invalid command line\n  3  chunk not found\n  4  invalid CRC\n  5  not a PNG file"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: PngMsgArgs,
+}
+
+impl Cli {
+    /// Parses the command line like [`Parser::parse`], and also rejects the
+    /// option combinations that depend on a chunk type's value, which clap's
+    /// own argument rules can't express, the same way clap rejects the rest.
+    pub fn parse_checked() -> Cli {
+        let cli = Cli::parse();
+        let (name, conflict) = match &cli.command {
+            PngMsgArgs::Encode(args) => ("encode", args.conflict()),
+            PngMsgArgs::Decode(args) => ("decode", args.conflict()),
+            PngMsgArgs::Embed(args) => ("embed", args.conflict()),
+            _ => return cli,
+        };
+        if let Some(message) = conflict {
+            let mut command = Cli::command();
+            command.build();
+            let subcommand = command.find_subcommand_mut(name).expect("every subcommand is registered");
+            subcommand.error(ErrorKind::ArgumentConflict, message).exit();
+        }
+        cli
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PngMsgArgs {
+    /// Append a message chunk to a PNG file
+    Encode(EncodeArgs),
+    /// Print the message stored in the first chunk of the given type
+    Decode(DecodeArgs),
+    /// Remove the first chunk of the given type and rewrite the file
+    Remove(RemoveArgs),
+    /// Print every chunk in a PNG file
+    Print(PrintArgs),
+    /// Generate an identity for public-key encryption, or a signing key
+    Keygen(KeygenArgs),
+    /// Sign every chunk of the given type with an Ed25519 key
+    Sign(SignArgs),
+    /// Check the signatures on chunks of the given type
+    Verify(VerifyArgs),
+    /// Hide a message in a PNG file, either in a chunk or in the pixels
+    Embed(EmbedArgs),
+    /// Print a message hidden with `embed`
+    Extract(ExtractArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct EncodeArgs {
+    /// PNG file to read
+    pub file_path: PathBuf,
+    /// Four letter chunk type to store the message under, e.g. "ruSt"
+    pub chunk_type: String,
+    /// Message to embed
+    pub message: String,
+    /// Where to write the result; the input file is overwritten when omitted
+    pub output: Option<PathBuf>,
+    /// Compress the message before storing it
+    #[arg(long, value_enum)]
+    pub compress: Option<CodecArg>,
+    /// Split the message across several chunks of at most this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub max_chunk_size: Option<usize>,
+    #[command(flatten)]
+    pub encryption: EncryptArgs,
+    /// How to store the message
+    #[arg(long = "as", value_enum, default_value_t = Format::Chunk)]
+    pub format: Format,
+    /// Store the message even if the chunk type is critical, public or has
+    /// the reserved bit set, with a warning instead of an error
+    #[arg(long)]
+    pub force: bool,
+    #[command(flatten)]
+    pub text: TextArgs,
+}
+
+impl EncodeArgs {
+    fn conflict(&self) -> Option<String> {
+        // chunk types that don't parse are reported by the command itself
+        let chunk_type = ChunkType::from_str(&self.chunk_type).ok();
+        if self.format == Format::Chunk {
+            if let Some(message) = chunk_type.as_ref().and_then(text_chunk_conflict) {
+                return Some(format!("{}, use '--as text' to write a text chunk", message));
+            }
+        }
+
+        let international = self.format == Format::Text && chunk_type.as_ref() == Some(&ChunkType::ITXT);
+        if !international {
+            if self.text.language.is_some() {
+                return Some("the argument '--language' can only be used with iTXt chunks and '--as text'".to_string());
+            }
+            if self.text.translated_keyword.is_some() {
+                return Some("the argument '--translated-keyword' can only be used with iTXt chunks and '--as text'".to_string());
+            }
+        }
+
+        if self.format != Format::Text {
+            return None;
+        }
+        let codec = Codec::from(self.compress?);
+        if chunk_type == Some(ChunkType::TEXT) {
+            return Some("the argument '--compress' cannot be used with tEXt chunks, use zTXt or iTXt".to_string());
+        }
+        if codec != Codec::Deflate {
+            return Some("text chunks only support '--compress deflate'".to_string());
+        }
+        None
+    }
+}
+
+/// Standard text chunks hold a keyword and Latin-1 or UTF-8 text, so message
+/// data is never stored under their types, not even with `--force`.
+fn text_chunk_conflict(chunk_type: &ChunkType) -> Option<String> {
+    text::is_text_chunk(chunk_type)
+        .then(|| format!("{} chunks can't hold message data", chunk_type))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Store the message data as is
+    Chunk,
+    /// Write a standard tEXt, zTXt or iTXt chunk, as picked by the chunk
+    /// type, that other PNG tools can read
+    Text,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct TextArgs {
+    /// Keyword of the text chunk, e.g. "Comment" or "Description"
+    #[arg(
+        long,
+        required_if_eq("format", "text"),
+        conflicts_with_all = ["password", "recipients", "recipients_files", "max_chunk_size"]
+    )]
+    pub keyword: Option<String>,
+    /// Language of an iTXt message, e.g. "en" or "pt-BR"
+    #[arg(long)]
+    pub language: Option<String>,
+    /// The keyword translated into the language of an iTXt message
+    #[arg(long)]
+    pub translated_keyword: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct DecodeArgs {
+    /// PNG file to read
+    pub file_path: PathBuf,
+    /// Chunk type the message was stored under
+    pub chunk_type: String,
+    /// Print every message stored under this type, one per line
+    #[arg(long)]
+    pub all: bool,
+    /// Only print tEXt, zTXt or iTXt chunks with this keyword
+    #[arg(long)]
+    pub keyword: Option<String>,
+    #[command(flatten)]
+    pub decryption: DecryptArgs,
+}
+
+impl DecodeArgs {
+    fn conflict(&self) -> Option<String> {
+        let chunk_type = ChunkType::from_str(&self.chunk_type);
+        if self.keyword.is_some() && chunk_type.is_ok_and(|chunk_type| !text::is_text_chunk(&chunk_type)) {
+            return Some(format!("the argument '--keyword' cannot be used with {} chunks, only tEXt, zTXt or iTXt", self.chunk_type));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct RemoveArgs {
+    /// PNG file to rewrite
+    pub file_path: PathBuf,
+    /// Chunk type to remove
+    pub chunk_type: String,
+    /// Remove every chunk of this type instead of only the first
+    #[arg(long)]
+    pub all: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PrintArgs {
+    /// PNG file to read
+    pub file_path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct KeygenArgs {
+    /// File to write the new key to; printed when omitted
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Generate an Ed25519 signing key instead of an encryption identity
+    #[arg(long)]
+    pub signing: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SignArgs {
+    /// PNG file to read
+    pub file_path: PathBuf,
+    /// Chunk type of the message to sign
+    pub chunk_type: String,
+    /// Where to write the result; the input file is overwritten when omitted
+    pub output: Option<PathBuf>,
+    /// Signing key file created with `keygen --signing`
+    #[arg(short, long)]
+    pub key: PathBuf,
+    /// Also sign the image header, palette and pixel data, so editing the
+    /// image invalidates the signature
+    #[arg(long)]
+    pub image: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// PNG file to read
+    pub file_path: PathBuf,
+    /// Chunk type of the signed message
+    pub chunk_type: String,
+    /// Only accept signatures by this verifying key; can be repeated
+    #[arg(long = "signer")]
+    pub signers: Vec<String>,
+    /// Only accept signatures by the verifying keys in this file; can be repeated
+    #[arg(long = "signers-file")]
+    pub signers_files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct EncryptArgs {
+    /// Encrypt the message with a key derived from this password
+    #[arg(long, env = "PNG_MSG_PASSWORD", hide_env_values = true)]
+    pub password: Option<String>,
+    /// Encrypt the message to this public key; can be repeated
+    #[arg(short = 'r', long = "recipient", conflicts_with = "password")]
+    pub recipients: Vec<String>,
+    /// Encrypt the message to every public key in this file; can be repeated
+    #[arg(short = 'R', long = "recipients-file", conflicts_with = "password")]
+    pub recipients_files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct DecryptArgs {
+    /// Password the message was encrypted with
+    #[arg(long, env = "PNG_MSG_PASSWORD", hide_env_values = true)]
+    pub password: Option<String>,
+    /// Identity file to try when decrypting; can be repeated
+    #[arg(short = 'i', long = "identity")]
+    pub identities: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct EmbedArgs {
+    /// PNG file to read
+    pub file_path: PathBuf,
+    /// Message to embed
+    pub message: String,
+    /// Where to write the result; the input file is overwritten when omitted
+    pub output: Option<PathBuf>,
+    /// Compress the message before storing it
+    #[arg(long, value_enum)]
+    pub compress: Option<CodecArg>,
+    /// Split the message across several chunks of at most this many bytes
+    #[arg(long, value_name = "BYTES")]
+    pub max_chunk_size: Option<usize>,
+    #[command(flatten)]
+    pub encryption: EncryptArgs,
+    /// Where to hide the message
+    #[arg(long, value_enum, default_value_t = Mode::Chunk)]
+    pub mode: Mode,
+    /// Chunk type to store the message under in chunk mode
+    #[arg(long, default_value = "ruSt")]
+    pub chunk_type: String,
+    /// Use the chunk type even if it is critical, public or has the reserved
+    /// bit set
+    #[arg(long)]
+    pub force: bool,
+    #[command(flatten)]
+    pub lsb: LsbArgs,
+}
+
+impl EmbedArgs {
+    fn conflict(&self) -> Option<String> {
+        if self.mode != Mode::Chunk {
+            return None;
+        }
+        ChunkType::from_str(&self.chunk_type).ok().as_ref().and_then(text_chunk_conflict)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ExtractArgs {
+    /// PNG file to read
+    pub file_path: PathBuf,
+    /// Where the message was hidden
+    #[arg(long, value_enum, default_value_t = Mode::Chunk)]
+    pub mode: Mode,
+    /// Chunk type the message was stored under in chunk mode
+    #[arg(long, default_value = "ruSt")]
+    pub chunk_type: String,
+    #[command(flatten)]
+    pub decryption: DecryptArgs,
+    #[command(flatten)]
+    pub lsb: LsbArgs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// Store the message in its own ancillary chunk
+    Chunk,
+    /// Store the message in the least significant bits of the pixels
+    Lsb,
+}
+
+#[derive(Debug, Args)]
+pub struct LsbArgs {
+    /// Low bits of each sample to use in lsb mode
+    #[arg(long, default_value_t = 1)]
+    pub bits: u8,
+    /// Channels to use in lsb mode; defaults to every colour channel but alpha
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub channels: Vec<ChannelArg>,
+}
+
+impl LsbArgs {
+    pub fn options(&self, color_type: ColorType) -> LsbOptions {
+        if self.channels.is_empty() {
+            return LsbOptions { bits_per_channel: self.bits, ..LsbOptions::default_for(color_type) };
+        }
+        LsbOptions::new(self.bits, self.channels.iter().map(|&channel| channel.into()).collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CodecArg {
+    Deflate,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl From<CodecArg> for Codec {
+    fn from(codec: CodecArg) -> Self {
+        match codec {
+            CodecArg::Deflate => Codec::Deflate,
+            #[cfg(feature = "zstd")]
+            CodecArg::Zstd => Codec::Zstd,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChannelArg {
+    Gray,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl From<ChannelArg> for Channel {
+    fn from(channel: ChannelArg) -> Self {
+        match channel {
+            ChannelArg::Gray => Channel::Gray,
+            ChannelArg::Red => Channel::Red,
+            ChannelArg::Green => Channel::Green,
+            ChannelArg::Blue => Channel::Blue,
+            ChannelArg::Alpha => Channel::Alpha,
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use std::fmt::Display;
-use crate::{Error,Result};
+use std::io::Write;
+use crate::Result;
+use crate::chunk_ref::ChunkRef;
 use crate::chunk_type::ChunkType;
-use crc::crc32::checksum_ieee;
 
 #[derive(Debug,PartialEq)]
 pub struct Chunk{
@@ -16,77 +17,75 @@ impl Chunk {
     pub const CRC_BYTES: usize =  4;
 
     pub const METADATA_BYTES: usize  = 
-        Chunk::DATA_LENGTH_BYTES + Chunk::CHUNK_TYPE_BYTES + Chunk::CHUNK_TYPE_BYTES;
+        Chunk::DATA_LENGTH_BYTES + Chunk::CHUNK_TYPE_BYTES + Chunk::CRC_BYTES;
+
+    /// Largest data length the PNG spec allows in a chunk's length field.
+    pub const MAX_DATA_LENGTH: usize = (1 << 31) - 1;
     
 
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk{
-        return Chunk {chunk_type: chunk_type, data};
+        Chunk {chunk_type, data}
+    }
+
+    /// Builds a chunk from its already separated fields, checking the CRC
+    /// stored in the file against the one computed from the type and data.
+    pub(crate) fn from_parts(chunk_type: ChunkType, data: Vec<u8>, expected_crc: u32) -> std::result::Result<Chunk, ChunkError> {
+        let new = Self { chunk_type, data };
+
+        let actual_crc = new.crc();
+
+        if expected_crc != actual_crc {
+            return Err(ChunkError::InvalidCrc(expected_crc, actual_crc));
+        }
+        Ok(new)
     }
 
     pub fn length(&self) -> usize{
-        return self.data.len();
+        self.data.len()
     }
 
     pub fn chunk_type(&self) -> &ChunkType{
-        return &self.chunk_type;
+        &self.chunk_type
     }
 
-    fn data(&self) -> &[u8]{
-        return &self.data;
+    pub fn data(&self) -> &[u8]{
+        &self.data
     }
 
     pub fn data_as_string(&self) -> Result<String>{
         let s = std::str::from_utf8(&self.data)?;
-        return Ok(s.to_string());
+        Ok(s.to_string())
     }
 
-    fn crc(&self) -> u32{
-        let bytes: Vec<u8> = self.chunk_type.bytes().iter().chain(self.data.iter()).copied().collect();
-        checksum_ieee(&bytes)
+    pub fn crc(&self) -> u32{
+        self.as_chunk_ref().crc()
+    }
+
+    /// Borrows this chunk as a [`ChunkRef`].
+    pub fn as_chunk_ref(&self) -> ChunkRef<'_> {
+        ChunkRef::new(self.chunk_type.clone(), &self.data)
     }
 
     pub fn as_bytes(&self) -> Vec<u8>{
         let data_length = self.length() as u32;
-        return data_length.to_be_bytes().iter().chain(self.chunk_type.bytes().iter()).chain(self.data.iter()).chain(self.crc().to_be_bytes().iter()).copied().collect();
+        data_length.to_be_bytes().iter().chain(self.chunk_type.bytes().iter()).chain(self.data.iter()).chain(self.crc().to_be_bytes().iter()).copied().collect()
+    }
+
+    /// Writes the same bytes as [`Chunk::as_bytes`] without first collecting
+    /// them into a buffer.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&(self.length() as u32).to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.data)?;
+        writer.write_all(&self.crc().to_be_bytes())
     }
  }
 
 impl TryFrom<&[u8]> for Chunk{
-    type Error = Error;
+    type Error = ChunkError;
     
-    fn try_from(value: &[u8]) -> Result<Self> {
-
-        if value.len() < Chunk::METADATA_BYTES {
-            return Err(Box::from(ChunkError::InputTooSmall));
-        }
-
-        let (data_length,value) = value.split_at(Chunk::DATA_LENGTH_BYTES);
-        let data_length = u32::from_be_bytes(data_length.try_into()?) as usize;
-
-        let (chunk_type_bytes,value) = value.split_at(Chunk::CHUNK_TYPE_BYTES);
-
-        let chunk_type_bytes: [u8;4] = chunk_type_bytes.try_into()?;
-        let chunk_type: ChunkType =ChunkType::try_from(chunk_type_bytes)?;
-
-        if !chunk_type.is_valid() {
-            return Err(Box::from(ChunkError::InvalidChunkType));
-        }
-
-        let (data,value) = value.split_at(data_length);
-        let (crc_bytes, _) = value.split_at(Chunk::CRC_BYTES);
-
-        let new = Self {
-            chunk_type,
-            data: data.into(),
-        };
-
-        let actual_crc = new.crc();
-        let expected_crc = u32::from_be_bytes(crc_bytes.try_into()?);
-
-        if expected_crc != actual_crc {
-            return Err(Box::from(ChunkError::InvalidCrc(expected_crc, actual_crc)));
-        }
-        return Ok(new);
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        ChunkRef::try_from(value).map(|chunk| chunk.to_chunk())
     }
 }
 
@@ -96,6 +95,10 @@ pub enum ChunkError {
     InputTooSmall,
     InvalidCrc(u32,u32),
     InvalidChunkType,
+    /// The length field is larger than the PNG spec allows.
+    LengthOutOfRange(usize),
+    /// The length field claims more data than the input holds.
+    Truncated { declared: usize, remaining: usize },
 }
 
 impl std::error::Error for ChunkError {}
@@ -113,19 +116,23 @@ impl Display for ChunkError {
                 expected, actual
             ),
             ChunkError::InvalidChunkType => write!(f, "Invalid chunk type"),
+            ChunkError::LengthOutOfRange(length) => write!(
+                f,
+                "Declared length {} exceeds the maximum chunk length of {}",
+                length, Chunk::MAX_DATA_LENGTH
+            ),
+            ChunkError::Truncated { declared, remaining } => write!(
+                f,
+                "Declared length {} exceeds remaining {} bytes",
+                declared, remaining
+            ),
         }
     }
 }
 
 impl std::fmt::Display for Chunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Chunk {{",)?;
-        writeln!(f, "  Length: {}", self.length())?;
-        writeln!(f, "  Type: {}", self.chunk_type())?;
-        writeln!(f, "  Data: {} bytes", self.data().len())?;
-        writeln!(f, "  Crc: {}", self.crc())?;
-        writeln!(f, "}}",)?;
-        Ok(())
+        self.as_chunk_ref().fmt(f)
     }
 }
 
@@ -236,6 +243,72 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_empty_input_is_too_small() {
+        let chunk = Chunk::try_from([].as_ref());
+
+        assert!(matches!(chunk, Err(ChunkError::InputTooSmall)));
+    }
+
+    #[test]
+    fn test_truncated_chunk_data() {
+        let data_length: u32 = 42;
+        let chunk_type = "RuSt".as_bytes();
+        let message_bytes = "This is where".as_bytes();
+
+        let chunk_data: Vec<u8> = data_length
+            .to_be_bytes()
+            .iter()
+            .chain(chunk_type.iter())
+            .chain(message_bytes.iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(chunk, Err(ChunkError::Truncated { declared: 42, remaining: 9 })));
+    }
+
+    #[test]
+    fn test_truncated_chunk_crc() {
+        let data_length: u32 = 42;
+        let chunk_type = "RuSt".as_bytes();
+        let message_bytes = "This is where your secret message will be!".as_bytes();
+        let crc: u32 = 2882656334;
+
+        let chunk_data: Vec<u8> = data_length
+            .to_be_bytes()
+            .iter()
+            .chain(chunk_type.iter())
+            .chain(message_bytes.iter())
+            .chain(crc.to_be_bytes()[..2].iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(chunk, Err(ChunkError::Truncated { declared: 42, remaining: 40 })));
+    }
+
+    #[test]
+    fn test_oversized_chunk_length() {
+        let data_length: u32 = u32::MAX;
+        let chunk_type = "RuSt".as_bytes();
+        let crc: u32 = 2882656334;
+
+        let chunk_data: Vec<u8> = data_length
+            .to_be_bytes()
+            .iter()
+            .chain(chunk_type.iter())
+            .chain(crc.to_be_bytes().iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(chunk, Err(ChunkError::LengthOutOfRange(_))));
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
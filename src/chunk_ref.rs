@@ -0,0 +1,241 @@
+use std::fmt::Display;
+use crate::Result;
+use crate::chunk::{Chunk, ChunkError};
+use crate::chunk_type::ChunkType;
+use crate::png::{Png, PngError};
+use crate::registry;
+use crc::crc32::{checksum_ieee, update, IEEE_TABLE};
+
+/// A chunk whose data borrows from the buffer it was parsed out of.
+///
+/// Offers the same read-only API as [`Chunk`] without copying the data, which
+/// makes it the cheaper choice when a file is only being inspected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+}
+
+impl<'a> ChunkRef<'a> {
+    pub fn new(chunk_type: ChunkType, data: &'a [u8]) -> ChunkRef<'a> {
+        ChunkRef { chunk_type, data }
+    }
+
+    pub fn length(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn data_as_str(&self) -> Result<&'a str> {
+        Ok(std::str::from_utf8(self.data)?)
+    }
+
+    pub fn data_as_string(&self) -> Result<String> {
+        self.data_as_str().map(str::to_string)
+    }
+
+    pub fn crc(&self) -> u32 {
+        update(checksum_ieee(&self.chunk_type.bytes()), &IEEE_TABLE, self.data)
+    }
+
+    /// Copies the data into an owned [`Chunk`].
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(self.chunk_type.clone(), self.data.to_vec())
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = ChunkError;
+
+    fn try_from(value: &'a [u8]) -> std::result::Result<Self, Self::Error> {
+        if value.len() < Chunk::METADATA_BYTES {
+            return Err(ChunkError::InputTooSmall);
+        }
+
+        let (data_length, value) = value.split_first_chunk::<{ Chunk::DATA_LENGTH_BYTES }>().ok_or(ChunkError::InputTooSmall)?;
+        let data_length = u32::from_be_bytes(*data_length) as usize;
+
+        if data_length > Chunk::MAX_DATA_LENGTH {
+            return Err(ChunkError::LengthOutOfRange(data_length));
+        }
+
+        let (chunk_type_bytes, value) = value.split_first_chunk::<{ Chunk::CHUNK_TYPE_BYTES }>().ok_or(ChunkError::InputTooSmall)?;
+        let chunk_type: ChunkType = ChunkType::try_from(*chunk_type_bytes).map_err(|_| ChunkError::InvalidChunkType)?;
+
+        if !chunk_type.is_valid() {
+            return Err(ChunkError::InvalidChunkType);
+        }
+
+        // the data has to leave room for the trailing CRC
+        let remaining = value.len().saturating_sub(Chunk::CRC_BYTES);
+        if data_length > remaining {
+            return Err(ChunkError::Truncated { declared: data_length, remaining });
+        }
+
+        let (data, value) = value.split_at(data_length);
+        let (crc_bytes, _) = value.split_first_chunk::<{ Chunk::CRC_BYTES }>().ok_or(ChunkError::InputTooSmall)?;
+
+        let chunk = ChunkRef { chunk_type, data };
+
+        let expected_crc = u32::from_be_bytes(*crc_bytes);
+        let actual_crc = chunk.crc();
+
+        if expected_crc != actual_crc {
+            return Err(ChunkError::InvalidCrc(expected_crc, actual_crc));
+        }
+        Ok(chunk)
+    }
+}
+
+impl Display for ChunkRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Chunk {{",)?;
+        writeln!(f, "  Length: {}", self.length())?;
+        match registry::lookup(self.chunk_type()) {
+            Some(info) => writeln!(f, "  Type: {} ({})", self.chunk_type(), info.name)?,
+            None => writeln!(f, "  Type: {}", self.chunk_type())?,
+        }
+        match registry::decode(self.chunk_type(), self.data) {
+            Ok(Some(decoded)) => write!(f, "{}", decoded)?,
+            Ok(None) => writeln!(f, "  Data: {} bytes", self.data().len())?,
+            Err(err) => {
+                writeln!(f, "  Data: {} bytes", self.data().len())?;
+                writeln!(f, "  Error: {}", err)?;
+            }
+        }
+        writeln!(f, "  Crc: {}", self.crc())?;
+        writeln!(f, "}}",)?;
+        Ok(())
+    }
+}
+
+/// Iterates over the chunks of a complete PNG held in memory, borrowing each
+/// chunk's data from the buffer.
+pub struct ChunkRefs<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    index: usize,
+    done: bool,
+}
+
+impl<'a> ChunkRefs<'a> {
+    /// Checks the PNG signature at the start of `bytes`.
+    pub fn new(bytes: &'a [u8]) -> std::result::Result<ChunkRefs<'a>, PngError> {
+        let header = bytes.get(..Png::STANDARD_HEADER.len()).ok_or(PngError::TooSmall)?;
+
+        if header != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidHeader);
+        }
+
+        Ok(ChunkRefs {
+            bytes,
+            offset: Png::STANDARD_HEADER.len(),
+            index: 0,
+            done: false,
+        })
+    }
+}
+
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = std::result::Result<ChunkRef<'a>, PngError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.offset >= self.bytes.len() {
+            return None;
+        }
+
+        match ChunkRef::try_from(&self.bytes[self.offset..]) {
+            Ok(chunk) => {
+                // the chunk was parsed from these bytes, so this stays in bounds
+                self.offset += chunk.length() + Chunk::METADATA_BYTES;
+                self.index += 1;
+                Some(Ok(chunk))
+            }
+            Err(source) => {
+                self.done = true;
+                Some(Err(PngError::InvalidChunk {
+                    index: self.index,
+                    offset: self.offset,
+                    source,
+                }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        let chunks = [
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"I am the first chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("miDl").unwrap(), b"I am another chunk".to_vec()),
+        ];
+
+        Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(chunks.iter().flat_map(|chunk| chunk.as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_ref_borrows_input() {
+        let bytes = testing_bytes();
+        let chunk = ChunkRef::try_from(&bytes[Png::STANDARD_HEADER.len()..]).unwrap();
+
+        assert_eq!(chunk.chunk_type().to_string(), "FrSt");
+        assert_eq!(chunk.data_as_str().unwrap(), "I am the first chunk");
+        assert!(std::ptr::eq(chunk.data().as_ptr(), bytes[16..].as_ptr()));
+    }
+
+    #[test]
+    fn test_chunk_ref_matches_chunk() {
+        let bytes = testing_bytes();
+        let chunk_bytes = &bytes[Png::STANDARD_HEADER.len()..];
+
+        let chunk_ref = ChunkRef::try_from(chunk_bytes).unwrap();
+        let chunk = Chunk::try_from(chunk_bytes).unwrap();
+
+        assert_eq!(chunk_ref.length(), chunk.length());
+        assert_eq!(chunk_ref.crc(), chunk.crc());
+        assert_eq!(chunk_ref.to_chunk(), chunk);
+        assert_eq!(chunk_ref.to_string(), chunk.to_string());
+    }
+
+    #[test]
+    fn test_chunk_refs() {
+        let bytes = testing_bytes();
+        let types: Vec<String> = ChunkRefs::new(&bytes)
+            .unwrap()
+            .map(|chunk| chunk.unwrap().chunk_type().to_string())
+            .collect();
+
+        assert_eq!(types, ["FrSt", "miDl"]);
+    }
+
+    #[test]
+    fn test_chunk_refs_stops_after_error() {
+        let mut bytes = testing_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+
+        let mut chunks = ChunkRefs::new(&bytes).unwrap();
+
+        assert!(chunks.next().unwrap().is_ok());
+        assert!(matches!(
+            chunks.next(),
+            Some(Err(PngError::InvalidChunk { index: 1, source: ChunkError::InvalidCrc(..), .. }))
+        ));
+        assert!(chunks.next().is_none());
+    }
+}
@@ -1,43 +1,112 @@
 use std::{convert::TryFrom, fmt::Display, str::FromStr};
-use crate::Error;
-// use crate::Result;
-#[derive(Debug,Clone, PartialEq,Eq)]
+#[derive(Debug,Clone, PartialEq,Eq,Hash)]
 pub struct ChunkType {
     bytes : [u8;4],
 }
 
 impl ChunkType { 
+    pub const IHDR: ChunkType = ChunkType { bytes: *b"IHDR" };
+    pub const PLTE: ChunkType = ChunkType { bytes: *b"PLTE" };
+    pub const IDAT: ChunkType = ChunkType { bytes: *b"IDAT" };
+    pub const IEND: ChunkType = ChunkType { bytes: *b"IEND" };
+    pub const TEXT: ChunkType = ChunkType { bytes: *b"tEXt" };
+    pub const ZTXT: ChunkType = ChunkType { bytes: *b"zTXt" };
+    pub const ITXT: ChunkType = ChunkType { bytes: *b"iTXt" };
+    pub const ACTL: ChunkType = ChunkType { bytes: *b"acTL" };
+    pub const FCTL: ChunkType = ChunkType { bytes: *b"fcTL" };
+    pub const FDAT: ChunkType = ChunkType { bytes: *b"fdAT" };
+    pub const TIME: ChunkType = ChunkType { bytes: *b"tIME" };
+    /// Private chunk holding a detached message signature. It's unsafe to
+    /// copy because edits to the image can invalidate it.
+    pub const SIGNATURE: ChunkType = ChunkType { bytes: *b"siGN" };
+
     pub fn bytes(&self) -> [u8;4] {
-        return self.bytes;
+        self.bytes
     }
     
     pub fn is_valid(&self) -> bool {
-        let valid_chars: bool = self.bytes
-                                    .iter()
-                                    .all(|&b: &u8| (b >= b'a' && b < b'z' || (b >= b'A' && b <= b'Z')));
-        
-        return valid_chars && self.is_reserved_bit_valid();
+        let valid_chars: bool = self.bytes.iter().all(|b: &u8| b.is_ascii_alphabetic());
+
+        valid_chars && self.is_reserved_bit_valid()
     }
     
-    fn is_critical(&self) -> bool {
-        return (self.bytes[0] & 0x20) != 0x20;
+    pub fn is_critical(&self) -> bool {
+        (self.bytes[0] & 0x20) != 0x20
     }
     
-    fn is_public(&self) -> bool {
-        return (self.bytes[1] & 0x20) != 0x20;
+    pub fn is_public(&self) -> bool {
+        (self.bytes[1] & 0x20) != 0x20
     }
     
-    fn is_reserved_bit_valid(&self) -> bool {
-        return (self.bytes[2] & 0x20) != 0x20;
+    pub fn is_reserved_bit_valid(&self) -> bool {
+        (self.bytes[2] & 0x20) != 0x20
     }
     
-    fn is_safe_to_copy(&self) -> bool {
-        return (self.bytes[3] & 0x20) == 0x20;
+    pub fn is_safe_to_copy(&self) -> bool {
+        (self.bytes[3] & 0x20) == 0x20
+    }
+
+    /// Everything that makes this type a bad home for a user message.
+    /// Decoders refuse files with critical chunks they don't know, public
+    /// names are reserved for the spec, and the reserved bit must be clear.
+    pub fn message_policy_violations(&self) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        if self.is_critical() {
+            violations.push(PolicyViolation::Critical);
+        }
+        if self.is_public() {
+            violations.push(PolicyViolation::Public);
+        }
+        if !self.is_reserved_bit_valid() {
+            violations.push(PolicyViolation::ReservedBit);
+        }
+        violations
+    }
+
+    /// Checks that this type is private, ancillary and has the reserved bit
+    /// clear, so it can carry a message without breaking other decoders.
+    pub fn check_message_policy(&self) -> Result<(), ChunkTypeError> {
+        let violations = self.message_policy_violations();
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(ChunkTypeError::UnsuitableForMessage {
+            chunk_type: self.clone(),
+            violations,
+            suggestion: self.to_private_ancillary(),
+        })
+    }
+
+    /// The closest type that passes [`ChunkType::check_message_policy`]: the
+    /// same letters with the first two lowercased and the third uppercased.
+    /// The safe-to-copy letter is left as it is.
+    pub fn to_private_ancillary(&self) -> ChunkType {
+        let [a, b, c, d] = self.bytes;
+        ChunkType { bytes: [a | 0x20, b | 0x20, c & !0x20, d] }
+    }
+}
+
+/// A property bit that makes a chunk type unfit for user messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+    Critical,
+    Public,
+    ReservedBit,
+}
+
+impl Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyViolation::Critical => write!(f, "it is critical, so decoders that don't know it will reject the file"),
+            PolicyViolation::Public => write!(f, "it is public, a name reserved for chunks defined by the PNG spec"),
+            PolicyViolation::ReservedBit => write!(f, "its reserved bit is set, which makes it invalid"),
+        }
     }
 }
 
 impl TryFrom<[u8;4]> for ChunkType {
-    type Error = Error;
+    type Error = ChunkTypeError;
 
     fn try_from(value: [u8;4]) -> std::result::Result<Self, Self::Error> {
         Ok(Self {
@@ -48,23 +117,23 @@ impl TryFrom<[u8;4]> for ChunkType {
 
 
 impl FromStr for ChunkType {
-    type Err  = Error;
+    type Err  = ChunkTypeError;
 
     fn from_str(s: &str) -> Result<Self,Self::Err> {
         let bytes_arr: &[u8] = s.as_bytes();
 
         if bytes_arr.len() != 4 {
-            return Err(Box::new(ChunkTypeError::ByteLengthError(bytes_arr.len())));
+            return Err(ChunkTypeError::ByteLengthError(bytes_arr.len()));
         }
 
-        let valid_chars = bytes_arr.iter().all(|&b| (b >= b'a' && b <= b'z' || (b >= b'A' && b <= b'Z')));
+        let valid_chars = bytes_arr.iter().all(|b| b.is_ascii_alphabetic());
 
         if !valid_chars {
-            return Err(Box::new(ChunkTypeError::InvalidCharacter));
+            return Err(ChunkTypeError::InvalidCharacter);
         }
 
         let sized: [u8;4] = [bytes_arr[0],bytes_arr[1],bytes_arr[2],bytes_arr[3]];
-        Ok(ChunkType::try_from(sized)?)
+        ChunkType::try_from(sized)
      }
 }
 
@@ -75,11 +144,42 @@ impl std::fmt::Display for ChunkType {
     }
 }
 
+/// Anything a chunk's type can be looked up by: a [`ChunkType`] itself or
+/// its four letter name.
+pub trait MatchesChunkType {
+    fn matches_chunk_type(&self, chunk_type: &ChunkType) -> bool;
+}
+
+impl MatchesChunkType for ChunkType {
+    fn matches_chunk_type(&self, chunk_type: &ChunkType) -> bool {
+        self == chunk_type
+    }
+}
+
+impl MatchesChunkType for str {
+    fn matches_chunk_type(&self, chunk_type: &ChunkType) -> bool {
+        self.as_bytes() == chunk_type.bytes
+    }
+}
+
+impl MatchesChunkType for String {
+    fn matches_chunk_type(&self, chunk_type: &ChunkType) -> bool {
+        self.as_str().matches_chunk_type(chunk_type)
+    }
+}
+
+impl<T: MatchesChunkType + ?Sized> MatchesChunkType for &T {
+    fn matches_chunk_type(&self, chunk_type: &ChunkType) -> bool {
+        (**self).matches_chunk_type(chunk_type)
+    }
+}
+
 
 #[derive(Debug)]
 pub enum ChunkTypeError {
     ByteLengthError(usize),
-    InvalidCharacter
+    InvalidCharacter,
+    UnsuitableForMessage { chunk_type: ChunkType, violations: Vec<PolicyViolation>, suggestion: ChunkType },
 }
 
 impl std::error::Error for ChunkTypeError {}
@@ -94,6 +194,16 @@ impl Display for ChunkTypeError {
             ChunkTypeError::InvalidCharacter => {
                 write!(f,"Input contains one or more invalid characters")
             }
+            ChunkTypeError::UnsuitableForMessage { chunk_type, violations, suggestion } => {
+                write!(f, "{} should not hold a message: ", chunk_type)?;
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", violation)?;
+                }
+                write!(f, ". Try {} instead", suggestion)
+            }
         }
     }
 }
@@ -182,12 +292,60 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_with_z_is_valid() {
+        let chunk = ChunkType::try_from(*b"zTXt").unwrap();
+        assert!(chunk.is_valid());
+
+        let chunk = ChunkType::try_from(*b"RuSz").unwrap();
+        assert!(chunk.is_valid());
+    }
+
+    #[test]
+    pub fn test_message_policy() {
+        assert!(ChunkType::from_str("ruSt").unwrap().check_message_policy().is_ok());
+        assert!(ChunkType::from_str("ruST").unwrap().check_message_policy().is_ok());
+
+        let chunk = ChunkType::from_str("RUST").unwrap();
+        assert_eq!(chunk.message_policy_violations(), [PolicyViolation::Critical, PolicyViolation::Public]);
+        assert!(matches!(
+            chunk.check_message_policy(),
+            Err(ChunkTypeError::UnsuitableForMessage { suggestion, .. }) if suggestion.to_string() == "ruST"
+        ));
+
+        let chunk = ChunkType::from_str("tEXt").unwrap();
+        assert_eq!(chunk.message_policy_violations(), [PolicyViolation::Public]);
+
+        let chunk = ChunkType::from_str("ruse").unwrap();
+        assert_eq!(chunk.message_policy_violations(), [PolicyViolation::ReservedBit]);
+        assert_eq!(chunk.to_private_ancillary().to_string(), "ruSe");
+    }
+
+    #[test]
+    pub fn test_suggestion_passes_policy() {
+        for name in ["RUST", "IDAT", "rust", "RuSt", "abcd"] {
+            let suggestion = ChunkType::from_str(name).unwrap().to_private_ancillary();
+            assert!(suggestion.message_policy_violations().is_empty(), "{}", suggestion);
+            assert!(suggestion.is_valid());
+        }
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_matches_chunk_type() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert!("RuSt".matches_chunk_type(&chunk));
+        assert!(!"rust".matches_chunk_type(&chunk));
+        assert!(String::from("RuSt").matches_chunk_type(&chunk));
+        assert!(chunk.clone().matches_chunk_type(&chunk));
+        assert!(!ChunkType::IEND.matches_chunk_type(&chunk));
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
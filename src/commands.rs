@@ -0,0 +1,451 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use png_msg::Result;
+use png_msg::chunk_type::{ChunkType, MatchesChunkType};
+use png_msg::compression::{self, Codec};
+use png_msg::crypto::{self, CipherSuite, CryptoError};
+use png_msg::envelope::{self, Flags};
+use png_msg::keys::{self, Identity, Recipient, SigningKey, VerifyingKey};
+use png_msg::lsb;
+use png_msg::png::Png;
+use png_msg::png::PngError;
+use png_msg::reader::PngReader;
+use png_msg::signature::{self, SignatureError, SignatureStatus};
+use png_msg::split;
+use png_msg::structure;
+use png_msg::text::{self, CompressedText, InternationalText, Keyword, Text, TextError, TextualChunk};
+use png_msg::writer::PngWriter;
+use crate::args::{
+    DecodeArgs, DecryptArgs, EmbedArgs, EncodeArgs, EncryptArgs, ExtractArgs, Format, KeygenArgs, Mode, PrintArgs,
+    RemoveArgs, SignArgs, TextArgs, VerifyArgs,
+};
+
+type FileReader = PngReader<BufReader<File>>;
+type FileWriter = PngWriter<BufWriter<File>>;
+
+pub fn encode(args: EncodeArgs) -> Result<()> {
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    let chunks = match args.format {
+        Format::Chunk => {
+            if let Err(err) = chunk_type.check_message_policy() {
+                if !args.force {
+                    return Err(err.into());
+                }
+                eprintln!("Warning: {}", err);
+            }
+
+            let payload = seal(args.message, args.compress.map(Codec::from), &args.encryption)?;
+            split::to_chunks(&chunk_type, payload, args.max_chunk_size)?
+        }
+        Format::Text => vec![text_chunk(&chunk_type, &args)?.to_chunk()],
+    };
+
+    let output = args.output.as_deref().unwrap_or(&args.file_path);
+    rewrite_png(&args.file_path, output, |reader, writer| {
+        // the message goes right before IEND so strict decoders still see it
+        let mut message = Some(chunks);
+        writer.copy_chunks_with(reader, |chunk| match message.take() {
+            Some(mut message) if *chunk.chunk_type() == ChunkType::IEND => {
+                message.push(chunk);
+                message
+            }
+            other => {
+                message = other;
+                vec![chunk]
+            }
+        })?;
+
+        message.into_iter().flatten().try_for_each(|chunk| writer.write_chunk(&chunk))
+    })
+}
+
+pub fn decode(args: DecodeArgs) -> Result<()> {
+    if ChunkType::from_str(&args.chunk_type).is_ok_and(|chunk_type| text::is_text_chunk(&chunk_type)) {
+        return decode_text(args);
+    }
+
+    // a split message can end anywhere in the file, so every matching chunk
+    // is read before anything is printed
+    let mut data = Vec::new();
+    for chunk in open_png(&args.file_path)? {
+        let chunk = chunk?;
+        if args.chunk_type.matches_chunk_type(chunk.chunk_type()) {
+            data.push(chunk.data().to_vec());
+        }
+    }
+
+    // a message with bad parts is reported and skipped, and only fails the
+    // command when no other message could be read
+    let mut first_error = None;
+    let mut found = false;
+    for message in split::reassemble(data.iter().map(Vec::as_slice)) {
+        let message = match message {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("Warning: skipped a message: {}", err);
+                first_error.get_or_insert(err);
+                continue;
+            }
+        };
+
+        if let (Some(message_id), true) = (message.message_id, message.reordered) {
+            eprintln!("Warning: the {} parts of message {:016x} were out of order", message.parts, message_id);
+        }
+        println!("{}", open(&message.data, &args.decryption)?);
+        found = true;
+
+        if !args.all {
+            break;
+        }
+    }
+
+    match first_error {
+        _ if found => Ok(()),
+        Some(err) => Err(err.into()),
+        None => Err(PngError::UnknownChunkType.into()),
+    }
+}
+
+fn decode_text(args: DecodeArgs) -> Result<()> {
+    let mut found = false;
+    let mut first_error = None;
+
+    for chunk in open_png(&args.file_path)? {
+        let chunk = chunk?;
+        if !args.chunk_type.matches_chunk_type(chunk.chunk_type()) {
+            continue;
+        }
+
+        let text = match TextualChunk::try_from(&chunk) {
+            Ok(text) => text,
+            Err(err) => {
+                eprintln!("Warning: skipped a {} chunk: {}", chunk.chunk_type(), err);
+                first_error.get_or_insert(err);
+                continue;
+            }
+        };
+        if args.keyword.as_ref().is_some_and(|keyword| keyword != text.keyword().as_str()) {
+            continue;
+        }
+        println!("{}", text.text());
+        found = true;
+
+        if !args.all {
+            break;
+        }
+    }
+
+    match first_error {
+        _ if found => Ok(()),
+        Some(err) => Err(err.into()),
+        None => Err(PngError::UnknownChunkType.into()),
+    }
+}
+
+pub fn remove(args: RemoveArgs) -> Result<()> {
+    rewrite_png(&args.file_path, &args.file_path, |reader, writer| {
+        let mut chunk_types = Vec::new();
+        let mut removed = Vec::new();
+        writer.copy_chunks_with(reader, |chunk| {
+            let remove = (args.all || !removed.contains(&true)) && args.chunk_type.matches_chunk_type(chunk.chunk_type());
+            chunk_types.push(chunk.chunk_type().clone());
+            removed.push(remove);
+            if remove { vec![] } else { vec![chunk] }
+        })?;
+
+        if !removed.contains(&true) {
+            return Err(PngError::UnknownChunkType.into());
+        }
+        // the file is only replaced when removing the chunks keeps the order
+        // valid, so IHDR, IDAT or IEND can't be taken out this way
+        let chunk_types: Vec<&ChunkType> = chunk_types.iter().collect();
+        Ok(structure::check_removal(&chunk_types, &removed)?)
+    })
+}
+
+pub fn print(args: PrintArgs) -> Result<()> {
+    for chunk in open_png(&args.file_path)? {
+        print!("{}", chunk?);
+    }
+    Ok(())
+}
+
+pub fn embed(args: EmbedArgs) -> Result<()> {
+    if args.mode == Mode::Chunk {
+        return encode(EncodeArgs {
+            file_path: args.file_path,
+            chunk_type: args.chunk_type,
+            message: args.message,
+            output: args.output,
+            compress: args.compress,
+            max_chunk_size: args.max_chunk_size,
+            encryption: args.encryption,
+            format: Format::Chunk,
+            force: args.force,
+            text: TextArgs::default(),
+        });
+    }
+
+    let mut png = read_png(&args.file_path)?;
+    let ihdr = png.ihdr()?;
+    let options = args.lsb.options(ihdr.color_type);
+
+    let message = seal(args.message, args.compress.map(Codec::from), &args.encryption)?;
+    let capacity = lsb::capacity(&ihdr, &options)?;
+    eprintln!("Capacity: {} bytes, message: {} bytes", capacity, message.len());
+
+    let mut pixels = png.pixels()?;
+    lsb::embed(&ihdr, &mut pixels, &message, &options)?;
+    for chunk in png.set_pixels(&pixels)? {
+        eprintln!("Dropped {} chunk: it is not safe to copy once the image changes", chunk.chunk_type());
+    }
+
+    let output = args.output.as_deref().unwrap_or(&args.file_path);
+    write_atomically(output, |writer| png.chunks().iter().try_for_each(|chunk| writer.write_chunk(chunk)))
+}
+
+pub fn extract(args: ExtractArgs) -> Result<()> {
+    if args.mode == Mode::Chunk {
+        return decode(DecodeArgs {
+            file_path: args.file_path,
+            chunk_type: args.chunk_type,
+            all: false,
+            keyword: None,
+            decryption: args.decryption,
+        });
+    }
+
+    let png = read_png(&args.file_path)?;
+    let ihdr = png.ihdr()?;
+    let message = lsb::extract(&ihdr, &png.pixels()?, &args.lsb.options(ihdr.color_type))?;
+    println!("{}", open(&message, &args.decryption)?);
+    Ok(())
+}
+
+pub fn keygen(args: KeygenArgs) -> Result<()> {
+    let (contents, public_key) = if args.signing {
+        let key = SigningKey::generate()?;
+        (key.to_file_contents(), key.verifying_key().to_string())
+    } else {
+        let identity = Identity::generate()?;
+        (identity.to_file_contents(), identity.recipient().to_string())
+    };
+
+    match args.output {
+        Some(path) => {
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+            options.open(path)?.write_all(contents.as_bytes())?;
+            eprintln!("Public key: {}", public_key);
+        }
+        None => print!("{}", contents),
+    }
+    Ok(())
+}
+
+pub fn sign(args: SignArgs) -> Result<()> {
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    let key = keys::read_signing_key(&args.key)?;
+
+    let mut png = read_png(&args.file_path)?;
+    signature::sign(&mut png, &chunk_type, &key, args.image)?;
+    println!("Signed {} with key {}", chunk_type, key.verifying_key().key_id());
+
+    let output = args.output.as_deref().unwrap_or(&args.file_path);
+    write_atomically(output, |writer| png.chunks().iter().try_for_each(|chunk| writer.write_chunk(chunk)))
+}
+
+pub fn verify(args: VerifyArgs) -> Result<()> {
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    let mut trusted = Vec::new();
+    for signer in &args.signers {
+        trusted.push(VerifyingKey::from_str(signer)?);
+    }
+    for path in &args.signers_files {
+        trusted.extend(keys::read_verifying_keys(path)?);
+    }
+
+    // without trusted signers every signature has to be valid; with them, one
+    // valid signature by a trusted signer is enough and the rest are reported
+    let png = read_png(&args.file_path)?;
+    let mut all_valid = true;
+    let mut trusted_valid = false;
+
+    for verification in signature::verify(&png, &chunk_type)? {
+        let Some(signer) = &verification.signer else {
+            all_valid = false;
+            println!("Signature chunk that can't be read");
+            println!("  Status: {}", verification.status);
+            continue;
+        };
+        let is_trusted = trusted.is_empty() || trusted.contains(signer);
+        let is_valid = verification.status == SignatureStatus::Valid;
+        all_valid &= is_valid;
+        trusted_valid |= is_trusted && is_valid;
+
+        println!("Signature by {} ({})", signer.key_id(), signer);
+        println!("  Covers image data: {}", if verification.covers_image { "yes" } else { "no" });
+        if is_trusted {
+            println!("  Status: {}", verification.status);
+        } else {
+            println!("  Status: {}, but the signer is not trusted", verification.status);
+        }
+    }
+
+    let verified = if trusted.is_empty() { all_valid } else { trusted_valid };
+    if !verified {
+        return Err(SignatureError::VerificationFailed.into());
+    }
+    Ok(())
+}
+
+/// Builds the standard text chunk `chunk_type` names for `encode --as text`.
+fn text_chunk(chunk_type: &ChunkType, args: &EncodeArgs) -> Result<TextualChunk> {
+    let keyword = Keyword::from_str(args.text.keyword.as_deref().unwrap_or_default())?;
+
+    // zTXt is always compressed, and clap rejects --compress for tEXt
+    let text = match *chunk_type {
+        ChunkType::TEXT => TextualChunk::Text(Text::new(keyword, &args.message)?),
+        ChunkType::ZTXT => TextualChunk::Compressed(CompressedText::new(keyword, &args.message)?),
+        ChunkType::ITXT => {
+            let mut text = InternationalText::new(keyword, &args.message)?
+                .with_language_tag(args.text.language.as_deref().unwrap_or_default())?
+                .with_translated_keyword(args.text.translated_keyword.as_deref().unwrap_or_default())?;
+            text.compressed = args.compress.is_some();
+            TextualChunk::International(text)
+        }
+        _ => return Err(TextError::NotTextChunk(chunk_type.clone()).into()),
+    };
+    Ok(text)
+}
+
+/// Compresses the message if a codec was given, then encrypts it to the given
+/// recipients or with the given password, if any. The envelope records which
+/// of those happened.
+fn seal(message: String, compress: Option<Codec>, encryption: &EncryptArgs) -> Result<Vec<u8>> {
+    let compressed = compress.is_some();
+    let message = match compress {
+        Some(codec) => compression::compress(message.as_bytes(), codec)?,
+        None => message.into_bytes(),
+    };
+
+    let mut recipients = Vec::new();
+    for recipient in &encryption.recipients {
+        recipients.push(Recipient::from_str(recipient)?);
+    }
+    for path in &encryption.recipients_files {
+        recipients.extend(keys::read_recipients(path)?);
+    }
+
+    let encrypted = if !recipients.is_empty() {
+        crypto::encrypt_to_recipients(&message, &recipients)?
+    } else if let Some(password) = &encryption.password {
+        crypto::encrypt_with_password(&message, password.as_bytes())?
+    } else {
+        return Ok(envelope::wrap(Flags { compressed, ..Flags::default() }, &message));
+    };
+    Ok(envelope::wrap(Flags { encrypted: true, compressed, ..Flags::default() }, &encrypted))
+}
+
+/// Turns stored message data back into text, decrypting and decompressing
+/// it as needed.
+fn open(data: &[u8], decryption: &DecryptArgs) -> Result<String> {
+    let (flags, body) = envelope::unwrap(data)?;
+    let mut plaintext = match flags.encrypted {
+        true => decrypt(body, decryption)?,
+        false => body.to_vec(),
+    };
+    if flags.compressed {
+        plaintext = compression::decompress(&plaintext)?;
+    }
+    Ok(std::str::from_utf8(&plaintext)?.to_string())
+}
+
+fn decrypt(data: &[u8], decryption: &DecryptArgs) -> Result<Vec<u8>> {
+    let plaintext = match crypto::cipher_suite(data)? {
+        CipherSuite::PasswordArgon2idChaCha20Poly1305 => {
+            let password = decryption.password.as_deref().ok_or(CryptoError::PasswordRequired)?;
+            crypto::decrypt_with_password(data, password.as_bytes())?
+        }
+        CipherSuite::X25519ChaCha20Poly1305 => {
+            let mut identities = Vec::new();
+            for path in &decryption.identities {
+                identities.extend(keys::read_identities(path)?);
+            }
+            crypto::decrypt_with_identities(data, &identities)?
+        }
+    };
+    Ok(plaintext)
+}
+
+fn read_png(path: &Path) -> Result<Png> {
+    Ok(Png::try_from(fs::read(path)?.as_slice())?)
+}
+
+fn open_png(path: &Path) -> Result<FileReader> {
+    PngReader::new(BufReader::new(File::open(path)?))
+}
+
+/// Streams `input` through `rewrite` into `output`.
+///
+/// The result goes to a temporary file next to `output` that only replaces it
+/// once everything was written, so `input` and `output` may be the same file
+/// and a failed rewrite leaves the original untouched.
+fn rewrite_png<F>(input: &Path, output: &Path, rewrite: F) -> Result<()>
+where
+    F: FnOnce(FileReader, &mut FileWriter) -> Result<()>,
+{
+    let reader = open_png(input)?;
+    write_atomically(output, |writer| rewrite(reader, writer))
+}
+
+/// Writes a PNG to a temporary file next to `output` and moves it into place
+/// once `write` succeeded. A file replaced this way keeps its permissions.
+fn write_atomically<F>(output: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut FileWriter) -> Result<()>,
+{
+    let (file, temp_path) = create_temp_file(output)?;
+
+    let result = PngWriter::new(BufWriter::new(file)).and_then(|mut writer| {
+        write(&mut writer)?;
+        writer.finish()?;
+        match fs::metadata(output) {
+            Ok(metadata) => Ok(fs::set_permissions(&temp_path, metadata.permissions())?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    });
+
+    match result {
+        Ok(()) => Ok(fs::rename(&temp_path, output)?),
+        Err(err) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(err)
+        }
+    }
+}
+
+/// Creates a temporary file in the same directory as `path`. The name
+/// carries the process ID and a counter, and an existing file is never
+/// opened, so concurrent runs and unrelated files are left alone.
+fn create_temp_file(path: &Path) -> Result<(File, PathBuf)> {
+    let file_name = path.file_name().unwrap_or_default();
+    let mut attempt = 0u32;
+    loop {
+        let mut temp_name = file_name.to_os_string();
+        temp_name.push(format!(".{}-{}.tmp", std::process::id(), attempt));
+        let temp_path = path.with_file_name(temp_name);
+
+        match OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+            Ok(file) => return Ok((file, temp_path)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists && attempt < 1000 => attempt += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
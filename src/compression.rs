@@ -0,0 +1,218 @@
+use std::fmt::Display;
+use std::io::{Read, Write};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+/// Marks message data compressed by this module.
+pub const MAGIC: [u8; 3] = *b"pmz";
+/// Magic, codec byte and the big-endian uncompressed length.
+pub const HEADER_LENGTH: usize = MAGIC.len() + 1 + 4;
+/// Largest payload [`decompress`] will inflate, to stop decompression bombs.
+pub const DEFAULT_SIZE_LIMIT: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// zlib, the same format zTXt and iTXt chunks use.
+    Deflate = 1,
+    /// Zstandard, only available with the `zstd` feature.
+    Zstd = 2,
+}
+
+impl Codec {
+    /// Whether this build can compress and decompress with the codec.
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Codec::Deflate => true,
+            Codec::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+}
+
+impl TryFrom<u8> for Codec {
+    type Error = CompressionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Codec::Deflate),
+            2 => Ok(Codec::Zstd),
+            _ => Err(CompressionError::UnknownCodec(value)),
+        }
+    }
+}
+
+impl Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Codec::Deflate => write!(f, "deflate"),
+            Codec::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// Compresses `data` behind a header naming the codec and the original size.
+pub fn compress(data: &[u8], codec: Codec) -> Result<Vec<u8>, CompressionError> {
+    let length = u32::try_from(data.len()).map_err(|_| CompressionError::TooLarge { length: data.len(), limit: u32::MAX as usize })?;
+
+    let mut compressed = Vec::with_capacity(HEADER_LENGTH + data.len() / 2);
+    compressed.extend_from_slice(&MAGIC);
+    compressed.push(codec as u8);
+    compressed.extend_from_slice(&length.to_be_bytes());
+
+    match codec {
+        Codec::Deflate => {
+            let mut encoder = ZlibEncoder::new(compressed, Compression::best());
+            encoder.write_all(data).map_err(CompressionError::Io)?;
+            encoder.finish().map_err(CompressionError::Io)
+        }
+        #[cfg(feature = "zstd")]
+        Codec::Zstd => {
+            let mut encoder = zstd::Encoder::new(compressed, zstd::DEFAULT_COMPRESSION_LEVEL).map_err(CompressionError::Io)?;
+            encoder.write_all(data).map_err(CompressionError::Io)?;
+            encoder.finish().map_err(CompressionError::Io)
+        }
+        #[cfg(not(feature = "zstd"))]
+        Codec::Zstd => Err(CompressionError::UnsupportedCodec(codec)),
+    }
+}
+
+/// Reverses [`compress`], refusing payloads that would inflate past
+/// [`DEFAULT_SIZE_LIMIT`].
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    decompress_with_limit(data, DEFAULT_SIZE_LIMIT)
+}
+
+/// Reverses [`compress`]. The declared size is checked against `limit`
+/// before anything is inflated, and the decoder is never allowed to produce
+/// more than the declared size, whatever the stream itself contains.
+pub fn decompress_with_limit(data: &[u8], limit: usize) -> Result<Vec<u8>, CompressionError> {
+    let rest = data.strip_prefix(&MAGIC).ok_or(CompressionError::NotCompressed)?;
+    let (&codec, rest) = rest.split_first().ok_or(CompressionError::Truncated)?;
+    let (length, compressed) = rest.split_first_chunk::<4>().ok_or(CompressionError::Truncated)?;
+
+    let codec = Codec::try_from(codec)?;
+    let length = u32::from_be_bytes(*length) as usize;
+    if length > limit {
+        return Err(CompressionError::TooLarge { length, limit });
+    }
+
+    let mut decompressed = Vec::with_capacity(length);
+    let read = match codec {
+        Codec::Deflate => ZlibDecoder::new(compressed)
+            .take(length as u64 + 1)
+            .read_to_end(&mut decompressed),
+        #[cfg(feature = "zstd")]
+        Codec::Zstd => zstd::Decoder::new(compressed)
+            .and_then(|decoder| decoder.take(length as u64 + 1).read_to_end(&mut decompressed)),
+        #[cfg(not(feature = "zstd"))]
+        Codec::Zstd => return Err(CompressionError::UnsupportedCodec(codec)),
+    };
+    read.map_err(CompressionError::Io)?;
+
+    if decompressed.len() != length {
+        return Err(CompressionError::LengthMismatch { expected: length, actual: decompressed.len() });
+    }
+    Ok(decompressed)
+}
+
+#[derive(Debug)]
+pub enum CompressionError {
+    NotCompressed,
+    Truncated,
+    UnknownCodec(u8),
+    UnsupportedCodec(Codec),
+    TooLarge { length: usize, limit: usize },
+    /// The stream inflated to a different size than its header declared.
+    LengthMismatch { expected: usize, actual: usize },
+    Io(std::io::Error),
+}
+
+impl std::error::Error for CompressionError {}
+
+impl Display for CompressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionError::NotCompressed => write!(f, "The message is not compressed"),
+            CompressionError::Truncated => write!(f, "The compressed message is truncated"),
+            CompressionError::UnknownCodec(codec) => write!(f, "Unknown compression codec {}", codec),
+            CompressionError::UnsupportedCodec(codec) => {
+                write!(f, "This build does not support {} compression", codec)
+            }
+            CompressionError::TooLarge { length, limit } => write!(
+                f,
+                "The message would decompress to {} bytes, more than the limit of {}",
+                length, limit
+            ),
+            CompressionError::LengthMismatch { expected, actual } => write!(
+                f,
+                "The message should decompress to {} bytes but gave {}",
+                expected, actual
+            ),
+            CompressionError::Io(err) => write!(f, "Failed to decompress the message: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_message() -> Vec<u8> {
+        br#"{"entries": ["#.iter()
+            .chain(b"{\"level\": \"info\", \"msg\": \"all good\"}, ".repeat(100).iter())
+            .chain(b"{}]}")
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn test_deflate_round_trip() {
+        let message = testing_message();
+        let compressed = compress(&message, Codec::Deflate).unwrap();
+
+        assert!(compressed.starts_with(&MAGIC));
+        assert!(compressed.len() < message.len() / 10);
+        assert_eq!(decompress(&compressed).unwrap(), message);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_round_trip() {
+        let message = testing_message();
+        let compressed = compress(&message, Codec::Zstd).unwrap();
+
+        assert_eq!(compressed[MAGIC.len()], Codec::Zstd as u8);
+        assert_eq!(decompress(&compressed).unwrap(), message);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_zstd_unsupported() {
+        assert!(!Codec::Zstd.is_supported());
+        assert!(matches!(compress(b"data", Codec::Zstd), Err(CompressionError::UnsupportedCodec(Codec::Zstd))));
+    }
+
+    #[test]
+    fn test_declared_size_over_limit() {
+        let compressed = compress(&[0; 4096], Codec::Deflate).unwrap();
+        let result = decompress_with_limit(&compressed, 1024);
+        assert!(matches!(result, Err(CompressionError::TooLarge { length: 4096, limit: 1024 })));
+    }
+
+    #[test]
+    fn test_bomb_with_understated_size() {
+        // a megabyte of zeros claiming to be ten bytes
+        let mut compressed = compress(&vec![0; 1 << 20], Codec::Deflate).unwrap();
+        compressed[4..8].copy_from_slice(&10u32.to_be_bytes());
+
+        let result = decompress(&compressed);
+        assert!(matches!(result, Err(CompressionError::LengthMismatch { expected: 10, actual: 11 })));
+    }
+
+    #[test]
+    fn test_bad_headers() {
+        assert!(matches!(decompress(b"plain"), Err(CompressionError::NotCompressed)));
+        assert!(matches!(decompress(b"pmz\x01\x00"), Err(CompressionError::Truncated)));
+        assert!(matches!(decompress(b"pmz\x07\x00\x00\x00\x00"), Err(CompressionError::UnknownCodec(7))));
+    }
+}
@@ -0,0 +1,471 @@
+use std::fmt::Display;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+use crate::keys::{Identity, Recipient};
+
+/// Marks chunk data produced by this module.
+pub const MAGIC: [u8; 4] = *b"pmsg";
+/// Version of the header layout below.
+pub const VERSION: u8 = 1;
+
+pub const SALT_LENGTH: usize = 16;
+pub const NONCE_LENGTH: usize = 12;
+pub const KEY_LENGTH: usize = 32;
+
+/// Key derivation and cipher an encrypted payload was sealed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherSuite {
+    /// Argon2id from a password, then ChaCha20-Poly1305.
+    PasswordArgon2idChaCha20Poly1305 = 1,
+    /// A random key wrapped for each X25519 recipient, then ChaCha20-Poly1305.
+    X25519ChaCha20Poly1305 = 2,
+}
+
+impl TryFrom<u8> for CipherSuite {
+    type Error = CryptoError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(CipherSuite::PasswordArgon2idChaCha20Poly1305),
+            2 => Ok(CipherSuite::X25519ChaCha20Poly1305),
+            _ => Err(CryptoError::UnknownCipherSuite(value)),
+        }
+    }
+}
+
+/// Argon2id cost settings. They are stored in the header so they can be
+/// raised later without breaking old files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordParams {
+    /// Memory in KiB.
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordParams {
+    /// Upper limits accepted when decrypting, so a crafted header can't make
+    /// key derivation take unbounded memory or time.
+    pub const MAX_MEMORY_COST: u32 = 1 << 20;
+    pub const MAX_TIME_COST: u32 = 64;
+    pub const MAX_PARALLELISM: u32 = 16;
+
+    fn to_argon2(self) -> Result<Argon2<'static>, CryptoError> {
+        if self.memory_cost > PasswordParams::MAX_MEMORY_COST
+            || self.time_cost > PasswordParams::MAX_TIME_COST
+            || self.parallelism > PasswordParams::MAX_PARALLELISM
+        {
+            return Err(CryptoError::InvalidParams(self));
+        }
+
+        let params = Params::new(self.memory_cost, self.time_cost, self.parallelism, Some(KEY_LENGTH))
+            .map_err(|_| CryptoError::InvalidParams(self))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl Default for PasswordParams {
+    /// The Argon2id settings recommended by OWASP.
+    fn default() -> Self {
+        PasswordParams { memory_cost: 19 * 1024, time_cost: 2, parallelism: 1 }
+    }
+}
+
+/// Length of the header in front of the ciphertext: magic, version, suite,
+/// the three Argon2 parameters, salt and nonce.
+pub const PASSWORD_HEADER_LENGTH: usize = MAGIC.len() + 2 + 3 * 4 + SALT_LENGTH + NONCE_LENGTH;
+
+/// Length of one recipient stanza: the ephemeral public key followed by the
+/// wrapped message key and its tag.
+pub const STANZA_LENGTH: usize = 32 + KEY_LENGTH + 16;
+
+const STANZA_INFO: &[u8] = b"png-msg x25519 stanza v1";
+
+/// The cipher suite an encrypted payload says it was sealed with.
+pub fn cipher_suite(data: &[u8]) -> Result<CipherSuite, CryptoError> {
+    read_preamble(data).map(|(suite, _)| suite)
+}
+
+/// Encrypts `plaintext` under a key derived from `password` with the default
+/// Argon2id settings.
+pub fn encrypt_with_password(plaintext: &[u8], password: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    encrypt_with_password_params(plaintext, password, PasswordParams::default())
+}
+
+pub fn encrypt_with_password_params(
+    plaintext: &[u8],
+    password: &[u8],
+    params: PasswordParams,
+) -> Result<Vec<u8>, CryptoError> {
+    let mut salt = [0; SALT_LENGTH];
+    let mut nonce = [0; NONCE_LENGTH];
+    random_bytes(&mut salt)?;
+    random_bytes(&mut nonce)?;
+
+    let mut header = Vec::with_capacity(PASSWORD_HEADER_LENGTH);
+    header.extend_from_slice(&MAGIC);
+    header.push(VERSION);
+    header.push(CipherSuite::PasswordArgon2idChaCha20Poly1305 as u8);
+    header.extend_from_slice(&params.memory_cost.to_be_bytes());
+    header.extend_from_slice(&params.time_cost.to_be_bytes());
+    header.extend_from_slice(&params.parallelism.to_be_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let key = derive_key(password, &salt, params)?;
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &header })
+        .map_err(|_| CryptoError::Encryption)?;
+
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+/// Reverses [`encrypt_with_password`]. The whole header is authenticated, so
+/// a wrong password and a tampered payload both fail the same way.
+pub fn decrypt_with_password(data: &[u8], password: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let (suite, rest) = read_preamble(data)?;
+    if suite != CipherSuite::PasswordArgon2idChaCha20Poly1305 {
+        return Err(CryptoError::WrongCipherSuite(suite));
+    }
+
+    let (params, rest) = rest.split_first_chunk::<12>().ok_or(CryptoError::Truncated)?;
+    let (salt, rest) = rest.split_first_chunk::<SALT_LENGTH>().ok_or(CryptoError::Truncated)?;
+    let (nonce, ciphertext) = rest.split_first_chunk::<NONCE_LENGTH>().ok_or(CryptoError::Truncated)?;
+
+    let params = PasswordParams {
+        memory_cost: u32::from_be_bytes([params[0], params[1], params[2], params[3]]),
+        time_cost: u32::from_be_bytes([params[4], params[5], params[6], params[7]]),
+        parallelism: u32::from_be_bytes([params[8], params[9], params[10], params[11]]),
+    };
+
+    let key = derive_key(password, salt, params)?;
+    ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &data[..PASSWORD_HEADER_LENGTH] })
+        .map_err(|_| CryptoError::AuthenticationFailed)
+}
+
+/// Encrypts `plaintext` so that any one of `recipients` can decrypt it.
+///
+/// Like age, the message is sealed under a random key, and that key is
+/// wrapped once per recipient with a key agreed between a fresh ephemeral
+/// X25519 key and the recipient's public key. The stanzas don't name their
+/// recipient, so decrypting means trying each local identity on each stanza.
+pub fn encrypt_to_recipients(plaintext: &[u8], recipients: &[Recipient]) -> Result<Vec<u8>, CryptoError> {
+    if recipients.is_empty() {
+        return Err(CryptoError::NoRecipients);
+    }
+    let count = u16::try_from(recipients.len()).map_err(|_| CryptoError::TooManyRecipients(recipients.len()))?;
+
+    let mut file_key = Key::default();
+    let mut nonce = [0; NONCE_LENGTH];
+    random_bytes(&mut file_key)?;
+    random_bytes(&mut nonce)?;
+
+    let mut header = Vec::with_capacity(MAGIC.len() + 4 + recipients.len() * STANZA_LENGTH + NONCE_LENGTH);
+    header.extend_from_slice(&MAGIC);
+    header.push(VERSION);
+    header.push(CipherSuite::X25519ChaCha20Poly1305 as u8);
+    header.extend_from_slice(&count.to_be_bytes());
+
+    for recipient in recipients {
+        let mut ephemeral = [0; KEY_LENGTH];
+        random_bytes(&mut ephemeral)?;
+        let ephemeral = StaticSecret::from(ephemeral);
+        let ephemeral_public = PublicKey::from(&ephemeral);
+
+        let shared = ephemeral.diffie_hellman(&PublicKey::from(*recipient.as_bytes()));
+        if !shared.was_contributory() {
+            return Err(CryptoError::InvalidRecipient(*recipient));
+        }
+
+        let wrap_key = stanza_key(shared.as_bytes(), ephemeral_public.as_bytes(), recipient.as_bytes());
+        let wrapped = ChaCha20Poly1305::new(&wrap_key)
+            .encrypt(&Nonce::default(), file_key.as_slice())
+            .map_err(|_| CryptoError::Encryption)?;
+
+        header.extend_from_slice(ephemeral_public.as_bytes());
+        header.extend_from_slice(&wrapped);
+    }
+    header.extend_from_slice(&nonce);
+
+    let ciphertext = ChaCha20Poly1305::new(&file_key)
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &header })
+        .map_err(|_| CryptoError::Encryption)?;
+
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+/// Reverses [`encrypt_to_recipients`] with whichever of `identities` the
+/// message was encrypted to.
+pub fn decrypt_with_identities(data: &[u8], identities: &[Identity]) -> Result<Vec<u8>, CryptoError> {
+    let (suite, rest) = read_preamble(data)?;
+    if suite != CipherSuite::X25519ChaCha20Poly1305 {
+        return Err(CryptoError::WrongCipherSuite(suite));
+    }
+    if identities.is_empty() {
+        return Err(CryptoError::IdentityRequired);
+    }
+
+    let (count, rest) = rest.split_first_chunk::<2>().ok_or(CryptoError::Truncated)?;
+    let stanzas_length = u16::from_be_bytes(*count) as usize * STANZA_LENGTH;
+    if rest.len() < stanzas_length + NONCE_LENGTH {
+        return Err(CryptoError::Truncated);
+    }
+    let (stanzas, rest) = rest.split_at(stanzas_length);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+    let header = &data[..data.len() - ciphertext.len()];
+
+    let file_key = stanzas
+        .chunks_exact(STANZA_LENGTH)
+        .find_map(|stanza| identities.iter().find_map(|identity| unwrap_stanza(stanza, identity)))
+        .ok_or(CryptoError::NoMatchingIdentity)?;
+
+    ChaCha20Poly1305::new(&file_key)
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+        .map_err(|_| CryptoError::AuthenticationFailed)
+}
+
+/// Tries to recover the message key from one stanza. The AEAD tag on the
+/// wrapped key tells whether the stanza was meant for this identity.
+fn unwrap_stanza(stanza: &[u8], identity: &Identity) -> Option<Key> {
+    let (ephemeral_public, wrapped) = stanza.split_first_chunk::<32>()?;
+
+    let shared = identity.diffie_hellman(ephemeral_public);
+    if !shared.was_contributory() {
+        return None;
+    }
+
+    let wrap_key = stanza_key(shared.as_bytes(), ephemeral_public, identity.recipient().as_bytes());
+    let file_key = ChaCha20Poly1305::new(&wrap_key).decrypt(&Nonce::default(), wrapped).ok()?;
+    Some(*Key::from_slice(&file_key))
+}
+
+fn stanza_key(shared: &[u8; 32], ephemeral_public: &[u8; 32], recipient: &[u8; 32]) -> Key {
+    let mut salt = [0; 64];
+    salt[..32].copy_from_slice(ephemeral_public);
+    salt[32..].copy_from_slice(recipient);
+
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(STANZA_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+/// Checks the magic and version and returns the cipher suite with the rest
+/// of the data.
+fn read_preamble(data: &[u8]) -> Result<(CipherSuite, &[u8]), CryptoError> {
+    let rest = data.strip_prefix(&MAGIC).ok_or(CryptoError::NotEncrypted)?;
+    let (&[version, suite], rest) = rest.split_first_chunk::<2>().ok_or(CryptoError::Truncated)?;
+
+    if version != VERSION {
+        return Err(CryptoError::UnsupportedVersion(version));
+    }
+    Ok((CipherSuite::try_from(suite)?, rest))
+}
+
+fn derive_key(password: &[u8], salt: &[u8], params: PasswordParams) -> Result<Key, CryptoError> {
+    let mut key = Key::default();
+    params
+        .to_argon2()?
+        .hash_password_into(password, salt, &mut key)
+        .map_err(|_| CryptoError::InvalidParams(params))?;
+    Ok(key)
+}
+
+fn random_bytes(buf: &mut [u8]) -> Result<(), CryptoError> {
+    getrandom::getrandom(buf).map_err(|_| CryptoError::Random)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CryptoError {
+    NotEncrypted,
+    PasswordRequired,
+    IdentityRequired,
+    NoRecipients,
+    TooManyRecipients(usize),
+    InvalidRecipient(Recipient),
+    NoMatchingIdentity,
+    Truncated,
+    UnsupportedVersion(u8),
+    UnknownCipherSuite(u8),
+    WrongCipherSuite(CipherSuite),
+    InvalidParams(PasswordParams),
+    AuthenticationFailed,
+    Encryption,
+    Random,
+}
+
+impl std::error::Error for CryptoError {}
+
+impl Display for CryptoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CryptoError::NotEncrypted => write!(f, "The message is not encrypted"),
+            CryptoError::PasswordRequired => write!(f, "The message is encrypted and needs a password"),
+            CryptoError::IdentityRequired => {
+                write!(f, "The message is encrypted to public keys and needs an identity to decrypt")
+            }
+            CryptoError::NoRecipients => write!(f, "At least one recipient is needed"),
+            CryptoError::TooManyRecipients(count) => {
+                write!(f, "Can't encrypt to {} recipients, the limit is {}", count, u16::MAX)
+            }
+            CryptoError::InvalidRecipient(recipient) => write!(f, "{} is not a usable public key", recipient),
+            CryptoError::NoMatchingIdentity => {
+                write!(f, "None of the given identities can decrypt this message")
+            }
+            CryptoError::Truncated => write!(f, "The encrypted message is truncated"),
+            CryptoError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported encrypted message version {}", version)
+            }
+            CryptoError::UnknownCipherSuite(suite) => write!(f, "Unknown cipher suite {}", suite),
+            CryptoError::WrongCipherSuite(suite) => {
+                write!(f, "The message was encrypted with {:?}, which needs a different key", suite)
+            }
+            CryptoError::InvalidParams(params) => write!(
+                f,
+                "Invalid key derivation parameters: {} KiB, {} passes, {} lanes",
+                params.memory_cost, params.time_cost, params.parallelism
+            ),
+            CryptoError::AuthenticationFailed => {
+                write!(f, "Decryption failed: wrong password or the message was tampered with")
+            }
+            CryptoError::Encryption => write!(f, "Encryption failed"),
+            CryptoError::Random => write!(f, "Could not get random bytes from the operating system"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap settings so the tests don't spend their time in Argon2.
+    const TEST_PARAMS: PasswordParams = PasswordParams { memory_cost: 64, time_cost: 1, parallelism: 1 };
+
+    fn encrypt(plaintext: &[u8], password: &[u8]) -> Vec<u8> {
+        encrypt_with_password_params(plaintext, password, TEST_PARAMS).unwrap()
+    }
+
+    #[test]
+    fn test_password_round_trip() {
+        let sealed = encrypt(b"This is where your secret message will be!", b"hunter2");
+
+        assert!(sealed.starts_with(&MAGIC));
+        assert_eq!(sealed.len(), PASSWORD_HEADER_LENGTH + 42 + 16);
+        assert_eq!(
+            decrypt_with_password(&sealed, b"hunter2").unwrap(),
+            b"This is where your secret message will be!"
+        );
+    }
+
+    #[test]
+    fn test_fresh_salt_and_nonce() {
+        assert_ne!(encrypt(b"same", b"same"), encrypt(b"same", b"same"));
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let sealed = encrypt(b"secret", b"hunter2");
+        assert_eq!(decrypt_with_password(&sealed, b"hunter3"), Err(CryptoError::AuthenticationFailed));
+    }
+
+    #[test]
+    fn test_tampered_header() {
+        let mut sealed = encrypt(b"secret", b"hunter2");
+        // bump the time cost, which is only protected by being authenticated
+        sealed[13] += 1;
+        assert_eq!(decrypt_with_password(&sealed, b"hunter2"), Err(CryptoError::AuthenticationFailed));
+    }
+
+    #[test]
+    fn test_not_encrypted() {
+        assert_eq!(decrypt_with_password(b"plain text", b"pw"), Err(CryptoError::NotEncrypted));
+    }
+
+    #[test]
+    fn test_every_recipient_can_decrypt() {
+        let identities: Vec<Identity> = (0..3).map(|_| Identity::generate().unwrap()).collect();
+        let recipients: Vec<Recipient> = identities.iter().map(Identity::recipient).collect();
+
+        let sealed = encrypt_to_recipients(b"team secret", &recipients).unwrap();
+        assert_eq!(cipher_suite(&sealed), Ok(CipherSuite::X25519ChaCha20Poly1305));
+
+        for identity in &identities {
+            let decrypted = decrypt_with_identities(&sealed, std::slice::from_ref(identity)).unwrap();
+            assert_eq!(decrypted, b"team secret");
+        }
+    }
+
+    #[test]
+    fn test_tries_every_identity() {
+        let ours = Identity::generate().unwrap();
+        let others: Vec<Identity> = (0..2).map(|_| Identity::generate().unwrap()).collect();
+
+        let sealed = encrypt_to_recipients(b"secret", &[others[0].recipient(), ours.recipient()]).unwrap();
+        let decrypted = decrypt_with_identities(&sealed, &[others[1].clone(), ours]).unwrap();
+        assert_eq!(decrypted, b"secret");
+    }
+
+    #[test]
+    fn test_no_matching_identity() {
+        let recipient = Identity::generate().unwrap().recipient();
+        let stranger = Identity::generate().unwrap();
+
+        let sealed = encrypt_to_recipients(b"secret", &[recipient]).unwrap();
+        assert_eq!(decrypt_with_identities(&sealed, &[stranger]), Err(CryptoError::NoMatchingIdentity));
+        assert_eq!(decrypt_with_identities(&sealed, &[]), Err(CryptoError::IdentityRequired));
+    }
+
+    #[test]
+    fn test_tampered_recipient_payload() {
+        let identity = Identity::generate().unwrap();
+        let mut sealed = encrypt_to_recipients(b"secret", &[identity.recipient()]).unwrap();
+
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert_eq!(decrypt_with_identities(&sealed, &[identity]), Err(CryptoError::AuthenticationFailed));
+    }
+
+    #[test]
+    fn test_suites_are_not_interchangeable() {
+        let identity = Identity::generate().unwrap();
+        let to_recipient = encrypt_to_recipients(b"secret", &[identity.recipient()]).unwrap();
+        let with_password = encrypt(b"secret", b"hunter2");
+
+        assert_eq!(
+            decrypt_with_password(&to_recipient, b"hunter2"),
+            Err(CryptoError::WrongCipherSuite(CipherSuite::X25519ChaCha20Poly1305))
+        );
+        assert_eq!(
+            decrypt_with_identities(&with_password, &[identity]),
+            Err(CryptoError::WrongCipherSuite(CipherSuite::PasswordArgon2idChaCha20Poly1305))
+        );
+        assert_eq!(encrypt_to_recipients(b"secret", &[]), Err(CryptoError::NoRecipients));
+    }
+
+    #[test]
+    fn test_bad_headers() {
+        let sealed = encrypt(b"secret", b"hunter2");
+
+        assert_eq!(decrypt_with_password(&sealed[..20], b"hunter2"), Err(CryptoError::Truncated));
+
+        let mut wrong_version = sealed.clone();
+        wrong_version[4] = 9;
+        assert_eq!(decrypt_with_password(&wrong_version, b"hunter2"), Err(CryptoError::UnsupportedVersion(9)));
+
+        let mut wrong_suite = sealed.clone();
+        wrong_suite[5] = 200;
+        assert_eq!(decrypt_with_password(&wrong_suite, b"hunter2"), Err(CryptoError::UnknownCipherSuite(200)));
+
+        let mut huge_memory = sealed;
+        huge_memory[6] = 0xFF;
+        assert!(matches!(decrypt_with_password(&huge_memory, b"hunter2"), Err(CryptoError::InvalidParams(_))));
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt::Display;
+
+/// Marks message data written by this crate. 0x89 can't start a UTF-8
+/// sequence, so no plain text message, which has to be valid UTF-8 to be
+/// read back, ever looks like an envelope.
+pub const MAGIC: [u8; 3] = [0x89, b'P', b'M'];
+/// Version of the header layout below.
+pub const VERSION: u8 = 1;
+/// Magic, version and flags.
+pub const HEADER_LENGTH: usize = MAGIC.len() + 2;
+
+/// How the body of an envelope was transformed, so reading it back never
+/// depends on guessing from the body's first bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flags {
+    /// The body is sealed by [`crate::crypto`].
+    pub encrypted: bool,
+    /// The message was compressed by [`crate::compression`] before it was
+    /// encrypted, if it was.
+    pub compressed: bool,
+    /// The body is one part of a message split by [`crate::split`], and the
+    /// joined parts are a message with an envelope of its own.
+    pub part: bool,
+}
+
+impl Flags {
+    const ENCRYPTED: u8 = 1 << 0;
+    const COMPRESSED: u8 = 1 << 1;
+    const PART: u8 = 1 << 2;
+
+    fn to_byte(self) -> u8 {
+        let mut byte = 0;
+        if self.encrypted {
+            byte |= Flags::ENCRYPTED;
+        }
+        if self.compressed {
+            byte |= Flags::COMPRESSED;
+        }
+        if self.part {
+            byte |= Flags::PART;
+        }
+        byte
+    }
+}
+
+impl TryFrom<u8> for Flags {
+    type Error = EnvelopeError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        if byte & !(Flags::ENCRYPTED | Flags::COMPRESSED | Flags::PART) != 0 {
+            return Err(EnvelopeError::UnknownFlags(byte));
+        }
+        Ok(Flags {
+            encrypted: byte & Flags::ENCRYPTED != 0,
+            compressed: byte & Flags::COMPRESSED != 0,
+            part: byte & Flags::PART != 0,
+        })
+    }
+}
+
+/// Puts `body` behind an envelope header carrying `flags`.
+pub fn wrap(flags: Flags, body: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(HEADER_LENGTH + body.len());
+    data.extend_from_slice(&MAGIC);
+    data.push(VERSION);
+    data.push(flags.to_byte());
+    data.extend_from_slice(body);
+    data
+}
+
+/// Splits message data into its flags and body. Data without an envelope,
+/// written by older versions or other tools, is a plain body as it is.
+pub fn unwrap(data: &[u8]) -> Result<(Flags, &[u8]), EnvelopeError> {
+    let Some(rest) = data.strip_prefix(&MAGIC) else {
+        return Ok((Flags::default(), data));
+    };
+    let (&[version, flags], body) = rest.split_first_chunk::<2>().ok_or(EnvelopeError::Truncated)?;
+    if version != VERSION {
+        return Err(EnvelopeError::UnsupportedVersion(version));
+    }
+    Ok((Flags::try_from(flags)?, body))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EnvelopeError {
+    Truncated,
+    UnsupportedVersion(u8),
+    UnknownFlags(u8),
+}
+
+impl std::error::Error for EnvelopeError {}
+
+impl Display for EnvelopeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvelopeError::Truncated => write!(f, "The message header is truncated"),
+            EnvelopeError::UnsupportedVersion(version) => write!(f, "Unsupported message version {}", version),
+            EnvelopeError::UnknownFlags(flags) => write!(f, "Unknown message flags {:#04x}", flags),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::{self, Codec};
+    use crate::crypto::{self, PasswordParams};
+
+    #[test]
+    fn test_plain_message_round_trip() {
+        let data = wrap(Flags::default(), b"pmsg is our tag");
+
+        assert_eq!(data.len(), HEADER_LENGTH + 15);
+        assert_eq!(unwrap(&data), Ok((Flags::default(), b"pmsg is our tag".as_slice())));
+    }
+
+    #[test]
+    fn test_encrypted_message_round_trip() {
+        let params = PasswordParams { memory_cost: 64, time_cost: 1, parallelism: 1 };
+        let sealed = crypto::encrypt_with_password_params(b"pmsg is our tag", b"hunter2", params).unwrap();
+        let data = wrap(Flags { encrypted: true, ..Flags::default() }, &sealed);
+
+        let (flags, body) = unwrap(&data).unwrap();
+        assert!(flags.encrypted);
+        assert_eq!(crypto::decrypt_with_password(body, b"hunter2").unwrap(), b"pmsg is our tag");
+    }
+
+    #[test]
+    fn test_compressed_message_round_trip() {
+        let plain = wrap(Flags::default(), b"pmz notes");
+        assert_eq!(unwrap(&plain), Ok((Flags::default(), b"pmz notes".as_slice())));
+
+        let compressed = compression::compress(b"pmz notes", Codec::Deflate).unwrap();
+        let data = wrap(Flags { compressed: true, ..Flags::default() }, &compressed);
+
+        let (flags, body) = unwrap(&data).unwrap();
+        assert_eq!(flags, Flags { compressed: true, ..Flags::default() });
+        assert_eq!(compression::decompress(body).unwrap(), b"pmz notes");
+    }
+
+    #[test]
+    fn test_data_without_envelope_is_plain() {
+        assert_eq!(unwrap(b"pmsg is our tag"), Ok((Flags::default(), b"pmsg is our tag".as_slice())));
+        assert_eq!(unwrap(b""), Ok((Flags::default(), b"".as_slice())));
+    }
+
+    #[test]
+    fn test_bad_headers() {
+        assert_eq!(unwrap(b"\x89PM\x01"), Err(EnvelopeError::Truncated));
+        assert_eq!(unwrap(b"\x89PM\x07\x00"), Err(EnvelopeError::UnsupportedVersion(7)));
+        assert_eq!(unwrap(b"\x89PM\x01\x80"), Err(EnvelopeError::UnknownFlags(0x80)));
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt::Display;
+use crate::apng::ApngError;
+use crate::chunk::ChunkError;
+use crate::chunk_type::ChunkTypeError;
+use crate::compression::CompressionError;
+use crate::crypto::CryptoError;
+use crate::envelope::EnvelopeError;
+use crate::ihdr::IhdrError;
+use crate::keys::KeyError;
+use crate::lsb::LsbError;
+use crate::pixels::PixelError;
+use crate::png::PngError;
+use crate::registry::RegistryError;
+use crate::signature::SignatureError;
+use crate::split::SplitError;
+use crate::structure::StructureError;
+use crate::text::TextError;
+
+/// Every error the crate can produce, grouped by the layer it came from so
+/// callers can branch on the kind instead of matching on messages.
+#[derive(Debug)]
+pub enum Error {
+    ChunkType(ChunkTypeError),
+    Chunk(ChunkError),
+    Png(PngError),
+    Registry(RegistryError),
+    Structure(StructureError),
+    Ihdr(IhdrError),
+    Pixel(PixelError),
+    Lsb(LsbError),
+    Crypto(CryptoError),
+    Envelope(EnvelopeError),
+    Key(KeyError),
+    Signature(SignatureError),
+    Compression(CompressionError),
+    Split(SplitError),
+    Text(TextError),
+    Apng(ApngError),
+    Utf8(std::str::Utf8Error),
+    Io(std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ChunkType(err) => Some(err),
+            Error::Chunk(err) => Some(err),
+            Error::Png(err) => Some(err),
+            Error::Registry(err) => Some(err),
+            Error::Structure(err) => Some(err),
+            Error::Ihdr(err) => Some(err),
+            Error::Pixel(err) => Some(err),
+            Error::Lsb(err) => Some(err),
+            Error::Crypto(err) => Some(err),
+            Error::Envelope(err) => Some(err),
+            Error::Key(err) => Some(err),
+            Error::Signature(err) => Some(err),
+            Error::Compression(err) => Some(err),
+            Error::Split(err) => Some(err),
+            Error::Text(err) => Some(err),
+            Error::Apng(err) => Some(err),
+            Error::Utf8(err) => Some(err),
+            Error::Io(err) => Some(err),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ChunkType(err) => write!(f, "{}", err),
+            Error::Chunk(err) => write!(f, "{}", err),
+            Error::Png(err) => write!(f, "{}", err),
+            Error::Registry(err) => write!(f, "{}", err),
+            Error::Structure(err) => write!(f, "{}", err),
+            Error::Ihdr(err) => write!(f, "{}", err),
+            Error::Pixel(err) => write!(f, "{}", err),
+            Error::Lsb(err) => write!(f, "{}", err),
+            Error::Crypto(err) => write!(f, "{}", err),
+            Error::Envelope(err) => write!(f, "{}", err),
+            Error::Key(err) => write!(f, "{}", err),
+            Error::Signature(err) => write!(f, "{}", err),
+            Error::Compression(err) => write!(f, "{}", err),
+            Error::Split(err) => write!(f, "{}", err),
+            Error::Text(err) => write!(f, "{}", err),
+            Error::Apng(err) => write!(f, "{}", err),
+            Error::Utf8(err) => write!(f, "Chunk data is not valid UTF-8: {}", err),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<ChunkTypeError> for Error {
+    fn from(err: ChunkTypeError) -> Self {
+        Error::ChunkType(err)
+    }
+}
+
+impl From<ChunkError> for Error {
+    fn from(err: ChunkError) -> Self {
+        Error::Chunk(err)
+    }
+}
+
+impl From<PngError> for Error {
+    fn from(err: PngError) -> Self {
+        Error::Png(err)
+    }
+}
+
+impl From<RegistryError> for Error {
+    fn from(err: RegistryError) -> Self {
+        Error::Registry(err)
+    }
+}
+
+impl From<StructureError> for Error {
+    fn from(err: StructureError) -> Self {
+        Error::Structure(err)
+    }
+}
+
+impl From<IhdrError> for Error {
+    fn from(err: IhdrError) -> Self {
+        Error::Ihdr(err)
+    }
+}
+
+impl From<PixelError> for Error {
+    fn from(err: PixelError) -> Self {
+        Error::Pixel(err)
+    }
+}
+
+impl From<LsbError> for Error {
+    fn from(err: LsbError) -> Self {
+        Error::Lsb(err)
+    }
+}
+
+impl From<CryptoError> for Error {
+    fn from(err: CryptoError) -> Self {
+        Error::Crypto(err)
+    }
+}
+
+impl From<EnvelopeError> for Error {
+    fn from(err: EnvelopeError) -> Self {
+        Error::Envelope(err)
+    }
+}
+
+impl From<KeyError> for Error {
+    fn from(err: KeyError) -> Self {
+        Error::Key(err)
+    }
+}
+
+impl From<SignatureError> for Error {
+    fn from(err: SignatureError) -> Self {
+        Error::Signature(err)
+    }
+}
+
+impl From<CompressionError> for Error {
+    fn from(err: CompressionError) -> Self {
+        Error::Compression(err)
+    }
+}
+
+impl From<SplitError> for Error {
+    fn from(err: SplitError) -> Self {
+        Error::Split(err)
+    }
+}
+
+impl From<TextError> for Error {
+    fn from(err: TextError) -> Self {
+        Error::Text(err)
+    }
+}
+
+impl From<ApngError> for Error {
+    fn from(err: ApngError) -> Self {
+        Error::Apng(err)
+    }
+}
+
+impl From<std::str::Utf8Error> for Error {
+    fn from(err: std::str::Utf8Error) -> Self {
+        Error::Utf8(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
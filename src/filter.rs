@@ -0,0 +1,238 @@
+use crate::pixels::PixelError;
+
+/// The five scanline filters of PNG filter method 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None = 0,
+    Sub = 1,
+    Up = 2,
+    Average = 3,
+    Paeth = 4,
+}
+
+impl FilterType {
+    pub const ALL: [FilterType; 5] = [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ];
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(FilterType::None),
+            1 => Ok(FilterType::Sub),
+            2 => Ok(FilterType::Up),
+            3 => Ok(FilterType::Average),
+            4 => Ok(FilterType::Paeth),
+            _ => Err(value),
+        }
+    }
+}
+
+/// Reverses the filtering of `filtered`, which holds rows of one filter type
+/// byte followed by `stride` bytes, appending the raw rows to `out`.
+///
+/// `bpp` is the number of bytes per complete pixel, rounded up to one.
+pub fn unfilter(filtered: &[u8], stride: usize, bpp: usize, out: &mut Vec<u8>) -> Result<(), PixelError> {
+    if stride == 0 {
+        return Ok(());
+    }
+
+    let start = out.len();
+    for (row, line) in filtered.chunks(stride + 1).enumerate() {
+        let (&filter_type, line) = line.split_first().ok_or(PixelError::NotEnoughData)?;
+        if line.len() != stride {
+            return Err(PixelError::NotEnoughData);
+        }
+        let filter_type = FilterType::try_from(filter_type)
+            .map_err(|filter_type| PixelError::UnknownFilterType { row, filter_type })?;
+
+        let row_start = out.len();
+        out.extend_from_slice(line);
+
+        let (previous, current) = out[start..].split_at_mut(row_start - start);
+        let previous = previous.len().checked_sub(stride).map(|prev_start| &previous[prev_start..]);
+        unfilter_row(filter_type, current, previous, bpp);
+    }
+    Ok(())
+}
+
+/// Filters `raw`, which holds rows of `stride` bytes, appending each row to
+/// `out` behind its filter type byte.
+///
+/// With `filter_type` set to `None` every row picks the filter that gives the
+/// smallest sum of absolute differences, the heuristic the spec recommends.
+pub fn filter(raw: &[u8], stride: usize, bpp: usize, filter_type: Option<FilterType>, out: &mut Vec<u8>) {
+    if stride == 0 {
+        return;
+    }
+
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+
+    for (row, line) in raw.chunks(stride).enumerate() {
+        let previous = row.checked_sub(1).map(|prev| &raw[prev * stride..row * stride]);
+
+        let chosen = match filter_type {
+            Some(filter_type) => {
+                filter_row(filter_type, line, previous, bpp, &mut best);
+                filter_type
+            }
+            None => {
+                let mut best_type = FilterType::None;
+                let mut best_score = u64::MAX;
+
+                for filter_type in FilterType::ALL {
+                    filter_row(filter_type, line, previous, bpp, &mut candidate);
+                    let score = candidate.iter().map(|&b| (b as i8).unsigned_abs() as u64).sum();
+                    if score < best_score {
+                        best_score = score;
+                        best_type = filter_type;
+                        std::mem::swap(&mut best, &mut candidate);
+                    }
+                }
+                best_type
+            }
+        };
+
+        out.push(chosen as u8);
+        out.extend_from_slice(&best);
+    }
+}
+
+fn unfilter_row(filter_type: FilterType, current: &mut [u8], previous: Option<&[u8]>, bpp: usize) {
+    match filter_type {
+        FilterType::None => {}
+        FilterType::Sub => {
+            for i in bpp..current.len() {
+                current[i] = current[i].wrapping_add(current[i - bpp]);
+            }
+        }
+        FilterType::Up => {
+            if let Some(previous) = previous {
+                for (byte, up) in current.iter_mut().zip(previous) {
+                    *byte = byte.wrapping_add(*up);
+                }
+            }
+        }
+        FilterType::Average => {
+            for i in 0..current.len() {
+                let left = if i >= bpp { current[i - bpp] as u16 } else { 0 };
+                let up = previous.map_or(0, |p| p[i] as u16);
+                current[i] = current[i].wrapping_add(((left + up) / 2) as u8);
+            }
+        }
+        FilterType::Paeth => {
+            for i in 0..current.len() {
+                let left = if i >= bpp { current[i - bpp] } else { 0 };
+                let up = previous.map_or(0, |p| p[i]);
+                let up_left = if i >= bpp { previous.map_or(0, |p| p[i - bpp]) } else { 0 };
+                current[i] = current[i].wrapping_add(paeth(left, up, up_left));
+            }
+        }
+    }
+}
+
+fn filter_row(filter_type: FilterType, line: &[u8], previous: Option<&[u8]>, bpp: usize, out: &mut [u8]) {
+    for i in 0..line.len() {
+        let left = if i >= bpp { line[i - bpp] } else { 0 };
+        let up = previous.map_or(0, |p| p[i]);
+        let up_left = if i >= bpp { previous.map_or(0, |p| p[i - bpp]) } else { 0 };
+
+        let predictor = match filter_type {
+            FilterType::None => 0,
+            FilterType::Sub => left,
+            FilterType::Up => up,
+            FilterType::Average => ((left as u16 + up as u16) / 2) as u8,
+            FilterType::Paeth => paeth(left, up, up_left),
+        };
+        out[i] = line[i].wrapping_sub(predictor);
+    }
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_up_left = (estimate - up_left as i16).abs();
+
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_rows() -> Vec<u8> {
+        // 4 rows of 6 bytes, i.e. two 3 byte pixels per row
+        (0..24u32).map(|i| (i * 37 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_each_filter_round_trips() {
+        let raw = testing_rows();
+
+        for filter_type in FilterType::ALL {
+            let mut filtered = Vec::new();
+            filter(&raw, 6, 3, Some(filter_type), &mut filtered);
+            assert!(filtered.chunks(7).all(|row| row[0] == filter_type as u8));
+
+            let mut unfiltered = Vec::new();
+            unfilter(&filtered, 6, 3, &mut unfiltered).unwrap();
+            assert_eq!(unfiltered, raw, "filter {:?}", filter_type);
+        }
+    }
+
+    #[test]
+    fn test_adaptive_filter_round_trips() {
+        let raw = testing_rows();
+
+        let mut filtered = Vec::new();
+        filter(&raw, 6, 3, None, &mut filtered);
+
+        let mut unfiltered = Vec::new();
+        unfilter(&filtered, 6, 3, &mut unfiltered).unwrap();
+        assert_eq!(unfiltered, raw);
+    }
+
+    #[test]
+    fn test_unfilter_known_rows() {
+        #[rustfmt::skip]
+        let filtered = [
+            1, 10, 20, 5, 5,    // Sub
+            2, 1, 1, 1, 1,      // Up
+            3, 2, 2, 2, 2,      // Average
+            4, 0, 0, 1, 1,      // Paeth
+        ];
+
+        let mut raw = Vec::new();
+        unfilter(&filtered, 4, 2, &mut raw).unwrap();
+
+        #[rustfmt::skip]
+        assert_eq!(raw, [
+            10, 20, 15, 25,
+            11, 21, 16, 26,
+            7, 12, 13, 21,
+            7, 12, 14, 22,
+        ]);
+    }
+
+    #[test]
+    fn test_unknown_filter_type() {
+        let filtered = [0, 1, 2, 5, 1, 2];
+        let result = unfilter(&filtered, 2, 1, &mut Vec::new());
+        assert!(matches!(result, Err(PixelError::UnknownFilterType { row: 1, filter_type: 5 })));
+    }
+}
@@ -0,0 +1,270 @@
+use std::fmt::Display;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+
+/// The decoded contents of an IHDR chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: InterlaceMethod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale = 0,
+    Rgb = 2,
+    Indexed = 3,
+    GrayscaleAlpha = 4,
+    Rgba = 6,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterlaceMethod {
+    None = 0,
+    Adam7 = 1,
+}
+
+impl Ihdr {
+    pub const LENGTH: usize = 13;
+
+    pub fn to_bytes(&self) -> [u8; Ihdr::LENGTH] {
+        let mut bytes = [0; Ihdr::LENGTH];
+        bytes[0..4].copy_from_slice(&self.width.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.height.to_be_bytes());
+        bytes[8] = self.bit_depth;
+        bytes[9] = self.color_type as u8;
+        bytes[10] = self.compression_method;
+        bytes[11] = self.filter_method;
+        bytes[12] = self.interlace_method as u8;
+        bytes
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::IHDR, self.to_bytes().to_vec())
+    }
+}
+
+impl ColorType {
+    /// Number of samples that make up one pixel.
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    /// The bit depths the PNG spec allows for this color type.
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = IhdrError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(IhdrError::InvalidColorType(value)),
+        }
+    }
+}
+
+impl TryFrom<u8> for InterlaceMethod {
+    type Error = IhdrError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(InterlaceMethod::None),
+            1 => Ok(InterlaceMethod::Adam7),
+            _ => Err(IhdrError::InvalidInterlaceMethod(value)),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = IhdrError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let bytes: &[u8; Ihdr::LENGTH] = value.try_into().map_err(|_| IhdrError::InvalidLength(value.len()))?;
+
+        let width = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let height = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+
+        if width == 0 || width as usize > Chunk::MAX_DATA_LENGTH || height == 0 || height as usize > Chunk::MAX_DATA_LENGTH {
+            return Err(IhdrError::InvalidDimensions(width, height));
+        }
+
+        let bit_depth = bytes[8];
+        let color_type = ColorType::try_from(bytes[9])?;
+
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(IhdrError::InvalidBitDepth(bit_depth, color_type));
+        }
+
+        if bytes[10] != 0 {
+            return Err(IhdrError::InvalidCompressionMethod(bytes[10]));
+        }
+        if bytes[11] != 0 {
+            return Err(IhdrError::InvalidFilterMethod(bytes[11]));
+        }
+
+        Ok(Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: bytes[10],
+            filter_method: bytes[11],
+            interlace_method: InterlaceMethod::try_from(bytes[12])?,
+        })
+    }
+}
+
+impl Display for Ihdr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Image {{")?;
+        writeln!(f, "  Dimensions: {}x{}", self.width, self.height)?;
+        writeln!(f, "  Bit depth: {}", self.bit_depth)?;
+        writeln!(f, "  Color type: {}", self.color_type)?;
+        writeln!(f, "  Interlace: {}", self.interlace_method)?;
+        writeln!(f, "}}")?;
+        Ok(())
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorType::Grayscale => write!(f, "Grayscale"),
+            ColorType::Rgb => write!(f, "RGB"),
+            ColorType::Indexed => write!(f, "Indexed"),
+            ColorType::GrayscaleAlpha => write!(f, "Grayscale with alpha"),
+            ColorType::Rgba => write!(f, "RGBA"),
+        }
+    }
+}
+
+impl Display for InterlaceMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterlaceMethod::None => write!(f, "None"),
+            InterlaceMethod::Adam7 => write!(f, "Adam7"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IhdrError {
+    InvalidLength(usize),
+    InvalidDimensions(u32, u32),
+    InvalidBitDepth(u8, ColorType),
+    InvalidColorType(u8),
+    InvalidCompressionMethod(u8),
+    InvalidFilterMethod(u8),
+    InvalidInterlaceMethod(u8),
+}
+
+impl std::error::Error for IhdrError {}
+
+impl Display for IhdrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IhdrError::InvalidLength(length) => {
+                write!(f, "IHDR data must be {} bytes but is {}", Ihdr::LENGTH, length)
+            }
+            IhdrError::InvalidDimensions(width, height) => {
+                write!(f, "Invalid image dimensions {}x{}", width, height)
+            }
+            IhdrError::InvalidBitDepth(bit_depth, color_type) => {
+                write!(f, "Bit depth {} is not allowed for color type {}", bit_depth, color_type)
+            }
+            IhdrError::InvalidColorType(color_type) => write!(f, "Invalid color type {}", color_type),
+            IhdrError::InvalidCompressionMethod(method) => write!(f, "Unknown compression method {}", method),
+            IhdrError::InvalidFilterMethod(method) => write!(f, "Unknown filter method {}", method),
+            IhdrError::InvalidInterlaceMethod(method) => write!(f, "Unknown interlace method {}", method),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IHDR_BYTES: [u8; 13] = [0, 0, 0, 50, 0, 0, 0, 40, 8, 6, 0, 0, 0];
+
+    #[test]
+    fn test_ihdr_from_bytes() {
+        let ihdr = Ihdr::try_from(IHDR_BYTES.as_ref()).unwrap();
+
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 40);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!(ihdr.interlace_method, InterlaceMethod::None);
+    }
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let ihdr = Ihdr::try_from(IHDR_BYTES.as_ref()).unwrap();
+        assert_eq!(ihdr.to_bytes(), IHDR_BYTES);
+    }
+
+    #[test]
+    fn test_ihdr_invalid_length() {
+        let ihdr = Ihdr::try_from(&IHDR_BYTES[..12]);
+        assert_eq!(ihdr, Err(IhdrError::InvalidLength(12)));
+    }
+
+    #[test]
+    fn test_ihdr_zero_width() {
+        let mut bytes = IHDR_BYTES;
+        bytes[3] = 0;
+        assert_eq!(Ihdr::try_from(bytes.as_ref()), Err(IhdrError::InvalidDimensions(0, 40)));
+    }
+
+    #[test]
+    fn test_ihdr_palette_with_16_bit_depth() {
+        let mut bytes = IHDR_BYTES;
+        bytes[8] = 16;
+        bytes[9] = 3;
+        assert_eq!(Ihdr::try_from(bytes.as_ref()), Err(IhdrError::InvalidBitDepth(16, ColorType::Indexed)));
+    }
+
+    #[test]
+    fn test_ihdr_invalid_color_type() {
+        let mut bytes = IHDR_BYTES;
+        bytes[9] = 5;
+        assert_eq!(Ihdr::try_from(bytes.as_ref()), Err(IhdrError::InvalidColorType(5)));
+    }
+
+    #[test]
+    fn test_ihdr_invalid_methods() {
+        let mut bytes = IHDR_BYTES;
+        bytes[10] = 1;
+        assert_eq!(Ihdr::try_from(bytes.as_ref()), Err(IhdrError::InvalidCompressionMethod(1)));
+
+        let mut bytes = IHDR_BYTES;
+        bytes[11] = 1;
+        assert_eq!(Ihdr::try_from(bytes.as_ref()), Err(IhdrError::InvalidFilterMethod(1)));
+
+        let mut bytes = IHDR_BYTES;
+        bytes[12] = 2;
+        assert_eq!(Ihdr::try_from(bytes.as_ref()), Err(IhdrError::InvalidInterlaceMethod(2)));
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+
+/// Text form of a secret key is this prefix followed by 64 hex digits.
+pub const SECRET_KEY_PREFIX: &str = "PNG-MSG-SECRET-KEY-";
+/// Text form of a public key is this prefix followed by 64 hex digits.
+pub const PUBLIC_KEY_PREFIX: &str = "png-msg-pk-";
+/// Text form of an Ed25519 signing key.
+pub const SIGNING_KEY_PREFIX: &str = "PNG-MSG-SIGNING-KEY-";
+/// Text form of an Ed25519 verifying key.
+pub const VERIFYING_KEY_PREFIX: &str = "png-msg-vk-";
+
+const KEY_LENGTH: usize = 32;
+
+/// An X25519 secret key that can decrypt messages sent to its [`Recipient`].
+#[derive(Clone)]
+pub struct Identity {
+    secret: StaticSecret,
+}
+
+/// An X25519 public key messages can be encrypted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recipient {
+    key: PublicKey,
+}
+
+impl Identity {
+    pub fn generate() -> Result<Identity, KeyError> {
+        let mut bytes = [0; KEY_LENGTH];
+        getrandom::getrandom(&mut bytes).map_err(|_| KeyError::Random)?;
+        Ok(Identity::from_bytes(bytes))
+    }
+
+    pub fn from_bytes(bytes: [u8; KEY_LENGTH]) -> Identity {
+        Identity { secret: StaticSecret::from(bytes) }
+    }
+
+    pub fn to_bytes(&self) -> [u8; KEY_LENGTH] {
+        self.secret.to_bytes()
+    }
+
+    pub fn recipient(&self) -> Recipient {
+        Recipient { key: PublicKey::from(&self.secret) }
+    }
+
+    /// The contents of a new identity file: the secret key with its public
+    /// key in a comment above it.
+    pub fn to_file_contents(&self) -> String {
+        format!("# public key: {}\n{}\n", self.recipient(), self)
+    }
+
+    pub(crate) fn diffie_hellman(&self, public: &[u8; KEY_LENGTH]) -> SharedSecret {
+        self.secret.diffie_hellman(&PublicKey::from(*public))
+    }
+}
+
+impl Recipient {
+    pub fn from_bytes(bytes: [u8; KEY_LENGTH]) -> Recipient {
+        Recipient { key: PublicKey::from(bytes) }
+    }
+
+    pub fn as_bytes(&self) -> &[u8; KEY_LENGTH] {
+        self.key.as_bytes()
+    }
+}
+
+/// An Ed25519 key for signing messages.
+#[derive(Clone)]
+pub struct SigningKey {
+    key: ed25519_dalek::SigningKey,
+}
+
+/// The public half of a [`SigningKey`], used to check signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyingKey {
+    key: ed25519_dalek::VerifyingKey,
+}
+
+impl SigningKey {
+    pub fn generate() -> Result<SigningKey, KeyError> {
+        let mut bytes = [0; KEY_LENGTH];
+        getrandom::getrandom(&mut bytes).map_err(|_| KeyError::Random)?;
+        Ok(SigningKey::from_bytes(bytes))
+    }
+
+    pub fn from_bytes(bytes: [u8; KEY_LENGTH]) -> SigningKey {
+        SigningKey { key: ed25519_dalek::SigningKey::from_bytes(&bytes) }
+    }
+
+    pub fn to_bytes(&self) -> [u8; KEY_LENGTH] {
+        self.key.to_bytes()
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey { key: self.key.verifying_key() }
+    }
+
+    pub fn to_file_contents(&self) -> String {
+        format!("# verifying key: {}\n{}\n", self.verifying_key(), self)
+    }
+
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        ed25519_dalek::Signer::sign(&self.key, message).to_bytes()
+    }
+}
+
+impl VerifyingKey {
+    pub fn from_bytes(bytes: [u8; KEY_LENGTH]) -> Result<VerifyingKey, KeyError> {
+        ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+            .map(|key| VerifyingKey { key })
+            .map_err(|_| KeyError::InvalidVerifyingKey)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; KEY_LENGTH] {
+        self.key.as_bytes()
+    }
+
+    /// Short fingerprint to tell keys apart: the first 8 bytes of the
+    /// SHA-256 of the key, in hex.
+    pub fn key_id(&self) -> String {
+        to_hex(&Sha256::digest(self.as_bytes())[..8])
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &[u8; 64]) -> bool {
+        self.key.verify_strict(message, &ed25519_dalek::Signature::from_bytes(signature)).is_ok()
+    }
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey").field("verifying_key", &self.verifying_key()).finish()
+    }
+}
+
+impl Display for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", SIGNING_KEY_PREFIX, to_hex(&self.to_bytes()))
+    }
+}
+
+impl Display for VerifyingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", VERIFYING_KEY_PREFIX, to_hex(self.as_bytes()))
+    }
+}
+
+impl FromStr for SigningKey {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix(SIGNING_KEY_PREFIX).ok_or(KeyError::InvalidSigningKey)?;
+        from_hex(hex).map(SigningKey::from_bytes).ok_or(KeyError::InvalidSigningKey)
+    }
+}
+
+impl FromStr for VerifyingKey {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix(VERIFYING_KEY_PREFIX).ok_or(KeyError::InvalidVerifyingKey)?;
+        from_hex(hex).ok_or(KeyError::InvalidVerifyingKey).and_then(VerifyingKey::from_bytes)
+    }
+}
+
+impl std::fmt::Debug for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // never print the secret itself
+        f.debug_struct("Identity").field("recipient", &self.recipient()).finish()
+    }
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", SECRET_KEY_PREFIX, to_hex(&self.to_bytes()))
+    }
+}
+
+impl Display for Recipient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", PUBLIC_KEY_PREFIX, to_hex(self.as_bytes()))
+    }
+}
+
+impl FromStr for Identity {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix(SECRET_KEY_PREFIX).ok_or(KeyError::InvalidSecretKey)?;
+        from_hex(hex).map(Identity::from_bytes).ok_or(KeyError::InvalidSecretKey)
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix(PUBLIC_KEY_PREFIX).ok_or(KeyError::InvalidPublicKey)?;
+        from_hex(hex).map(Recipient::from_bytes).ok_or(KeyError::InvalidPublicKey)
+    }
+}
+
+/// Parses one key per line, skipping blank lines and `#` comments.
+pub fn parse_keys<T: FromStr<Err = KeyError>>(text: &str) -> Result<Vec<T>, KeyError> {
+    let mut keys = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        keys.push(line.parse().map_err(|_| KeyError::InvalidLine(index + 1))?);
+    }
+
+    if keys.is_empty() {
+        return Err(KeyError::NoKeys);
+    }
+    Ok(keys)
+}
+
+pub fn read_identities<P: AsRef<Path>>(path: P) -> crate::Result<Vec<Identity>> {
+    Ok(parse_keys(&fs::read_to_string(path)?)?)
+}
+
+pub fn read_recipients<P: AsRef<Path>>(path: P) -> crate::Result<Vec<Recipient>> {
+    Ok(parse_keys(&fs::read_to_string(path)?)?)
+}
+
+/// Reads a signing key file, which must hold exactly one key.
+pub fn read_signing_key<P: AsRef<Path>>(path: P) -> crate::Result<SigningKey> {
+    let mut keys: Vec<SigningKey> = parse_keys(&fs::read_to_string(path)?)?;
+    if keys.len() > 1 {
+        return Err(KeyError::MultipleKeys(keys.len()).into());
+    }
+    Ok(keys.remove(0))
+}
+
+pub fn read_verifying_keys<P: AsRef<Path>>(path: P) -> crate::Result<Vec<VerifyingKey>> {
+    Ok(parse_keys(&fs::read_to_string(path)?)?)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<[u8; KEY_LENGTH]> {
+    if hex.len() != KEY_LENGTH * 2 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut bytes = [0; KEY_LENGTH];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyError {
+    InvalidSecretKey,
+    InvalidPublicKey,
+    InvalidSigningKey,
+    InvalidVerifyingKey,
+    InvalidLine(usize),
+    NoKeys,
+    MultipleKeys(usize),
+    Random,
+}
+
+impl std::error::Error for KeyError {}
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyError::InvalidSecretKey => {
+                write!(f, "A secret key must be {} followed by 64 hex digits", SECRET_KEY_PREFIX)
+            }
+            KeyError::InvalidPublicKey => {
+                write!(f, "A public key must be {} followed by 64 hex digits", PUBLIC_KEY_PREFIX)
+            }
+            KeyError::InvalidSigningKey => {
+                write!(f, "A signing key must be {} followed by 64 hex digits", SIGNING_KEY_PREFIX)
+            }
+            KeyError::InvalidVerifyingKey => write!(
+                f,
+                "A verifying key must be {} followed by 64 hex digits of a valid Ed25519 key",
+                VERIFYING_KEY_PREFIX
+            ),
+            KeyError::InvalidLine(line) => write!(f, "Line {} of the key file is not a valid key", line),
+            KeyError::NoKeys => write!(f, "The key file contains no keys"),
+            KeyError::MultipleKeys(count) => write!(f, "Expected one key in the key file but found {}", count),
+            KeyError::Random => write!(f, "Could not get random bytes from the operating system"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_round_trip() {
+        let identity = Identity::generate().unwrap();
+        let parsed = Identity::from_str(&identity.to_string()).unwrap();
+
+        assert_eq!(parsed.to_bytes(), identity.to_bytes());
+        assert_eq!(parsed.recipient(), identity.recipient());
+    }
+
+    #[test]
+    fn test_recipient_round_trip() {
+        let recipient = Identity::generate().unwrap().recipient();
+        let text = recipient.to_string();
+
+        assert!(text.starts_with(PUBLIC_KEY_PREFIX));
+        assert_eq!(text.len(), PUBLIC_KEY_PREFIX.len() + 64);
+        assert_eq!(Recipient::from_str(&text), Ok(recipient));
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let recipient = Identity::generate().unwrap().recipient().to_string();
+
+        assert!(matches!(Identity::from_str(&recipient), Err(KeyError::InvalidSecretKey)));
+        assert_eq!(Recipient::from_str(&recipient[..recipient.len() - 1]), Err(KeyError::InvalidPublicKey));
+        let not_hex = format!("{}z", &recipient[..recipient.len() - 1]);
+        assert_eq!(Recipient::from_str(&not_hex), Err(KeyError::InvalidPublicKey));
+    }
+
+    #[test]
+    fn test_debug_hides_secret() {
+        let identity = Identity::generate().unwrap();
+        let debug = format!("{:?}", identity);
+        assert!(!debug.contains(&to_hex(&identity.to_bytes())));
+    }
+
+    #[test]
+    fn test_signing_key_round_trip() {
+        let key = SigningKey::generate().unwrap();
+        let parsed: Vec<SigningKey> = parse_keys(&key.to_file_contents()).unwrap();
+        assert_eq!(parsed[0].to_bytes(), key.to_bytes());
+
+        let verifying_key = key.verifying_key();
+        assert_eq!(VerifyingKey::from_str(&verifying_key.to_string()), Ok(verifying_key));
+        assert_eq!(verifying_key.key_id().len(), 16);
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SigningKey::generate().unwrap();
+        let signature = key.sign(b"message");
+
+        assert!(key.verifying_key().verify(b"message", &signature));
+        assert!(!key.verifying_key().verify(b"massage", &signature));
+        assert!(!SigningKey::generate().unwrap().verifying_key().verify(b"message", &signature));
+    }
+
+    #[test]
+    fn test_parse_key_file() {
+        let first = Identity::generate().unwrap();
+        let second = Identity::generate().unwrap();
+        let text = format!("{}\n\n{}", first.to_file_contents(), second.to_file_contents());
+
+        let identities: Vec<Identity> = parse_keys(&text).unwrap();
+        assert_eq!(identities.len(), 2);
+        assert_eq!(identities[1].recipient(), second.recipient());
+
+        assert!(matches!(parse_keys::<Identity>("# nothing here\n"), Err(KeyError::NoKeys)));
+        assert!(matches!(parse_keys::<Identity>("# comment\ngarbage\n"), Err(KeyError::InvalidLine(2))));
+    }
+}
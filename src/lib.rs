@@ -0,0 +1,25 @@
+pub mod adam7;
+pub mod apng;
+pub mod chunk;
+pub mod chunk_ref;
+pub mod chunk_type;
+pub mod compression;
+pub mod crypto;
+pub mod envelope;
+pub mod error;
+pub mod filter;
+pub mod ihdr;
+pub mod keys;
+pub mod lsb;
+pub mod mmap;
+pub mod pixels;
+pub mod png;
+pub mod reader;
+pub mod registry;
+pub mod signature;
+pub mod split;
+pub mod structure;
+pub mod text;
+pub mod writer;
+
+pub use error::{Error, Result};
@@ -0,0 +1,354 @@
+use std::fmt::Display;
+use crate::ihdr::{ColorType, Ihdr};
+
+/// Bytes in front of the message that hold its length.
+pub const LENGTH_PREFIX_BYTES: usize = 4;
+
+/// A colour channel that can carry message bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Channel {
+    Gray,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl Channel {
+    /// Position of this channel's sample within a pixel, or `None` if the
+    /// color type has no such channel.
+    pub fn index(&self, color_type: ColorType) -> Option<usize> {
+        match (color_type, self) {
+            (ColorType::Grayscale | ColorType::GrayscaleAlpha, Channel::Gray) => Some(0),
+            (ColorType::GrayscaleAlpha, Channel::Alpha) => Some(1),
+            (ColorType::Rgb | ColorType::Rgba, Channel::Red) => Some(0),
+            (ColorType::Rgb | ColorType::Rgba, Channel::Green) => Some(1),
+            (ColorType::Rgb | ColorType::Rgba, Channel::Blue) => Some(2),
+            (ColorType::Rgba, Channel::Alpha) => Some(3),
+            _ => None,
+        }
+    }
+
+    /// The channels that make up the visible colour, leaving alpha alone.
+    pub fn color_channels(color_type: ColorType) -> Vec<Channel> {
+        match color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => vec![Channel::Gray],
+            ColorType::Rgb | ColorType::Rgba => vec![Channel::Red, Channel::Green, Channel::Blue],
+            ColorType::Indexed => vec![],
+        }
+    }
+}
+
+impl Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Channel::Gray => write!(f, "gray"),
+            Channel::Red => write!(f, "red"),
+            Channel::Green => write!(f, "green"),
+            Channel::Blue => write!(f, "blue"),
+            Channel::Alpha => write!(f, "alpha"),
+        }
+    }
+}
+
+/// Where message bits go: the lowest `bits_per_channel` bits of every
+/// selected sample, pixel by pixel in row order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsbOptions {
+    pub bits_per_channel: u8,
+    pub channels: Vec<Channel>,
+}
+
+impl LsbOptions {
+    pub fn new(bits_per_channel: u8, channels: Vec<Channel>) -> LsbOptions {
+        LsbOptions { bits_per_channel, channels }
+    }
+
+    /// One bit in each colour channel, the least visible setting.
+    pub fn default_for(color_type: ColorType) -> LsbOptions {
+        LsbOptions::new(1, Channel::color_channels(color_type))
+    }
+}
+
+/// Number of message bytes the image can hold with these options, not
+/// counting the length prefix.
+pub fn capacity(ihdr: &Ihdr, options: &LsbOptions) -> Result<usize, LsbError> {
+    let layout = Layout::new(ihdr, options)?;
+    Ok(layout.capacity())
+}
+
+/// Hides `message` in the low bits of a decoded pixel buffer.
+pub fn embed(ihdr: &Ihdr, pixels: &mut [u8], message: &[u8], options: &LsbOptions) -> Result<(), LsbError> {
+    let layout = Layout::new(ihdr, options)?;
+    let capacity = layout.capacity();
+    if message.len() > capacity || message.len() > u32::MAX as usize {
+        return Err(LsbError::MessageTooLarge { length: message.len(), capacity });
+    }
+
+    let length = (message.len() as u32).to_be_bytes();
+    let mut slots = layout.byte_offsets();
+    let mask = layout.mask();
+
+    let mut acc: u32 = 0;
+    let mut acc_bits = 0;
+    let mut write = |value: u32| {
+        // capacity was checked above, so there is always a slot left
+        let offset = slots.next().unwrap();
+        pixels[offset] = (pixels[offset] & !mask) | value as u8;
+    };
+
+    for &byte in length.iter().chain(message) {
+        acc = (acc << 8) | byte as u32;
+        acc_bits += 8;
+        while acc_bits >= layout.bits {
+            acc_bits -= layout.bits;
+            write((acc >> acc_bits) & mask as u32);
+        }
+        acc &= (1 << acc_bits) - 1;
+    }
+    if acc_bits > 0 {
+        write((acc << (layout.bits - acc_bits)) & mask as u32);
+    }
+    Ok(())
+}
+
+/// Reads back a message hidden by [`embed`] with the same options.
+pub fn extract(ihdr: &Ihdr, pixels: &[u8], options: &LsbOptions) -> Result<Vec<u8>, LsbError> {
+    let layout = Layout::new(ihdr, options)?;
+    let capacity = layout.capacity();
+    let mask = layout.mask();
+
+    let mut bytes = layout.byte_offsets().scan((0u32, 0usize), |(acc, acc_bits), offset| {
+        *acc = (*acc << layout.bits) | (pixels[offset] & mask) as u32;
+        *acc_bits += layout.bits;
+        if *acc_bits < 8 {
+            return Some(None);
+        }
+        *acc_bits -= 8;
+        let byte = (*acc >> *acc_bits) as u8;
+        *acc &= (1 << *acc_bits) - 1;
+        Some(Some(byte))
+    }).flatten();
+
+    let mut length = [0; LENGTH_PREFIX_BYTES];
+    for byte in length.iter_mut() {
+        *byte = bytes.next().ok_or(LsbError::InvalidLength { length: 0, capacity })?;
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > capacity {
+        return Err(LsbError::InvalidLength { length, capacity });
+    }
+    Ok(bytes.take(length).collect())
+}
+
+/// The sample bytes that carry message bits, resolved for one image.
+struct Layout {
+    bits: usize,
+    pixel_count: usize,
+    channels: usize,
+    sample_bytes: usize,
+    indices: Vec<usize>,
+}
+
+impl Layout {
+    fn new(ihdr: &Ihdr, options: &LsbOptions) -> Result<Layout, LsbError> {
+        if ihdr.color_type == ColorType::Indexed || ihdr.bit_depth < 8 {
+            return Err(LsbError::UnsupportedFormat(ihdr.color_type, ihdr.bit_depth));
+        }
+        if !(1..=8).contains(&options.bits_per_channel) {
+            return Err(LsbError::InvalidBitsPerChannel(options.bits_per_channel));
+        }
+        if options.channels.is_empty() {
+            return Err(LsbError::NoChannels);
+        }
+
+        let mut indices = options
+            .channels
+            .iter()
+            .map(|channel| channel.index(ihdr.color_type).ok_or(LsbError::MissingChannel(*channel, ihdr.color_type)))
+            .collect::<Result<Vec<usize>, LsbError>>()?;
+        indices.sort_unstable();
+        indices.dedup();
+
+        Ok(Layout {
+            bits: options.bits_per_channel as usize,
+            pixel_count: ihdr.width as usize * ihdr.height as usize,
+            channels: ihdr.color_type.channels(),
+            sample_bytes: ihdr.bit_depth as usize / 8,
+            indices,
+        })
+    }
+
+    /// Message bytes that fit after the length prefix. Huge images saturate
+    /// instead of overflowing, since no message comes close to that size.
+    fn capacity(&self) -> usize {
+        let bits = self.pixel_count.saturating_mul(self.indices.len()).saturating_mul(self.bits);
+        (bits / 8).saturating_sub(LENGTH_PREFIX_BYTES)
+    }
+
+    fn mask(&self) -> u8 {
+        (0xFF_u16 >> (8 - self.bits)) as u8
+    }
+
+    /// Offsets of the least significant byte of each carrying sample. 16 bit
+    /// samples are big-endian, so that is the second of their two bytes.
+    fn byte_offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.pixel_count).flat_map(move |pixel| {
+            self.indices
+                .iter()
+                .map(move |index| ((pixel * self.channels + index) + 1) * self.sample_bytes - 1)
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LsbError {
+    UnsupportedFormat(ColorType, u8),
+    InvalidBitsPerChannel(u8),
+    NoChannels,
+    MissingChannel(Channel, ColorType),
+    MessageTooLarge { length: usize, capacity: usize },
+    /// The length prefix read from the pixels can't be right, which usually
+    /// means nothing was embedded or different options were used.
+    InvalidLength { length: usize, capacity: usize },
+}
+
+impl std::error::Error for LsbError {}
+
+impl Display for LsbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LsbError::UnsupportedFormat(color_type, bit_depth) => write!(
+                f,
+                "LSB embedding needs 8 or 16 bit samples that aren't palette indices, but the image is {} at bit depth {}",
+                color_type, bit_depth
+            ),
+            LsbError::InvalidBitsPerChannel(bits) => {
+                write!(f, "Bits per channel must be between 1 and 8, got {}", bits)
+            }
+            LsbError::NoChannels => write!(f, "At least one channel must be selected"),
+            LsbError::MissingChannel(channel, color_type) => {
+                write!(f, "{} images have no {} channel", color_type, channel)
+            }
+            LsbError::MessageTooLarge { length, capacity } => write!(
+                f,
+                "The message is {} bytes but the image can only hold {}",
+                length, capacity
+            ),
+            LsbError::InvalidLength { length, capacity } => write!(
+                f,
+                "No hidden message found: the stored length {} exceeds the capacity of {} bytes",
+                length, capacity
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::InterlaceMethod;
+
+    fn testing_ihdr(color_type: ColorType, bit_depth: u8) -> Ihdr {
+        Ihdr {
+            width: 16,
+            height: 8,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    fn testing_pixels(ihdr: &Ihdr) -> Vec<u8> {
+        let size = 16 * 8 * ihdr.color_type.channels() * ihdr.bit_depth as usize / 8;
+        (0..size).map(|i| (i * 7) as u8).collect()
+    }
+
+    #[test]
+    fn test_capacity() {
+        let ihdr = testing_ihdr(ColorType::Rgba, 8);
+
+        // 128 pixels * 3 channels * 1 bit = 48 bytes
+        assert_eq!(capacity(&ihdr, &LsbOptions::default_for(ihdr.color_type)), Ok(44));
+        // 128 pixels * 4 channels * 2 bits = 128 bytes
+        let options = LsbOptions::new(2, vec![Channel::Red, Channel::Green, Channel::Blue, Channel::Alpha]);
+        assert_eq!(capacity(&ihdr, &options), Ok(124));
+    }
+
+    #[test]
+    fn test_capacity_of_huge_image() {
+        let ihdr = Ihdr { width: u32::MAX, height: u32::MAX, ..testing_ihdr(ColorType::Rgba, 16) };
+        let options = LsbOptions::new(8, vec![Channel::Red, Channel::Green, Channel::Blue, Channel::Alpha]);
+        assert_eq!(capacity(&ihdr, &options), Ok(usize::MAX / 8 - LENGTH_PREFIX_BYTES));
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        for (color_type, bit_depth) in [(ColorType::Rgb, 8), (ColorType::Rgba, 16), (ColorType::Grayscale, 8), (ColorType::GrayscaleAlpha, 16)] {
+            for bits in 1..=8 {
+                let ihdr = testing_ihdr(color_type, bit_depth);
+                let options = LsbOptions::new(bits, Channel::color_channels(color_type));
+                let message = b"This is where your secret message will be!";
+                let message = &message[..message.len().min(capacity(&ihdr, &options).unwrap())];
+
+                let mut pixels = testing_pixels(&ihdr);
+                embed(&ihdr, &mut pixels, message, &options).unwrap();
+
+                assert_eq!(extract(&ihdr, &pixels, &options).unwrap(), message, "{} {} bits", color_type, bits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_embed_only_touches_low_bits_of_chosen_channels() {
+        let ihdr = testing_ihdr(ColorType::Rgba, 8);
+        let options = LsbOptions::new(2, vec![Channel::Blue]);
+        let original = testing_pixels(&ihdr);
+
+        let mut pixels = original.clone();
+        embed(&ihdr, &mut pixels, b"hidden", &options).unwrap();
+
+        for (i, (before, after)) in original.iter().zip(&pixels).enumerate() {
+            if i % 4 == 2 {
+                assert_eq!(before & !0b11, after & !0b11);
+            } else {
+                assert_eq!(before, after);
+            }
+        }
+    }
+
+    #[test]
+    fn test_message_too_large() {
+        let ihdr = testing_ihdr(ColorType::Grayscale, 8);
+        let mut pixels = testing_pixels(&ihdr);
+        let result = embed(&ihdr, &mut pixels, &[0; 13], &LsbOptions::default_for(ihdr.color_type));
+        assert_eq!(result, Err(LsbError::MessageTooLarge { length: 13, capacity: 12 }));
+    }
+
+    #[test]
+    fn test_extract_without_message() {
+        let ihdr = testing_ihdr(ColorType::Rgb, 8);
+        let pixels = vec![0xFF; 16 * 8 * 3];
+        let result = extract(&ihdr, &pixels, &LsbOptions::default_for(ihdr.color_type));
+        assert!(matches!(result, Err(LsbError::InvalidLength { .. })));
+    }
+
+    #[test]
+    fn test_unsupported_options() {
+        let ihdr = testing_ihdr(ColorType::Indexed, 8);
+        assert_eq!(
+            capacity(&ihdr, &LsbOptions::new(1, vec![Channel::Gray])),
+            Err(LsbError::UnsupportedFormat(ColorType::Indexed, 8))
+        );
+
+        let ihdr = testing_ihdr(ColorType::Rgb, 8);
+        assert_eq!(capacity(&ihdr, &LsbOptions::new(0, vec![Channel::Red])), Err(LsbError::InvalidBitsPerChannel(0)));
+        assert_eq!(capacity(&ihdr, &LsbOptions::new(1, vec![])), Err(LsbError::NoChannels));
+        assert_eq!(
+            capacity(&ihdr, &LsbOptions::new(1, vec![Channel::Alpha])),
+            Err(LsbError::MissingChannel(Channel::Alpha, ColorType::Rgb))
+        );
+    }
+}
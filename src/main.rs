@@ -1,12 +1,52 @@
 mod args;
-mod chunk;
-mod chunk_type;
 mod commands;
-mod png;
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
+use std::process::ExitCode;
+use args::{Cli, PngMsgArgs};
+use png_msg::Error;
+use png_msg::chunk::ChunkError;
+use png_msg::png::PngError;
 
-fn main() -> Result<()> {
-    todo!()
-}
\ No newline at end of file
+/// Exit status for failures that don't have a more specific code.
+const EXIT_FAILURE: u8 = 1;
+// 2 is left to clap, which exits with it on usage errors.
+/// The requested chunk type does not appear in the file.
+const EXIT_CHUNK_NOT_FOUND: u8 = 3;
+/// A chunk's stored CRC doesn't match its contents.
+const EXIT_INVALID_CRC: u8 = 4;
+/// The input doesn't start with the PNG signature.
+const EXIT_NOT_PNG: u8 = 5;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse_checked();
+
+    let result = match cli.command {
+        PngMsgArgs::Encode(args) => commands::encode(args),
+        PngMsgArgs::Decode(args) => commands::decode(args),
+        PngMsgArgs::Remove(args) => commands::remove(args),
+        PngMsgArgs::Print(args) => commands::print(args),
+        PngMsgArgs::Keygen(args) => commands::keygen(args),
+        PngMsgArgs::Sign(args) => commands::sign(args),
+        PngMsgArgs::Verify(args) => commands::verify(args),
+        PngMsgArgs::Embed(args) => commands::embed(args),
+        PngMsgArgs::Extract(args) => commands::extract(args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(exit_code(&err))
+        }
+    }
+}
+
+fn exit_code(err: &Error) -> u8 {
+    match err {
+        Error::Png(PngError::UnknownChunkType) => EXIT_CHUNK_NOT_FOUND,
+        Error::Png(PngError::InvalidHeader | PngError::TooSmall) => EXIT_NOT_PNG,
+        Error::Png(PngError::InvalidChunk { source: ChunkError::InvalidCrc(..), .. })
+        | Error::Chunk(ChunkError::InvalidCrc(..)) => EXIT_INVALID_CRC,
+        _ => EXIT_FAILURE,
+    }
+}
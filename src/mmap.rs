@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::path::Path;
+use memmap2::Mmap;
+use crate::chunk_ref::ChunkRefs;
+use crate::png::PngError;
+use crate::Result;
+
+/// A PNG file mapped into memory.
+///
+/// Chunks are handed out as [`ChunkRef`](crate::chunk_ref::ChunkRef)s that
+/// point straight into the mapping, so walking a file only touches the pages
+/// that are actually read and never copies chunk data.
+pub struct MappedPng {
+    mmap: Mmap,
+}
+
+impl MappedPng {
+    /// Maps the file at `path`. Use [`crate::reader::PngReader`] for files
+    /// that other processes may change.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated by this or any other
+    /// process while the `MappedPng` is alive. Writes through another handle
+    /// change bytes behind the `&[u8]` the chunks borrow from, and reading a
+    /// page past a truncated end kills the process with SIGBUS rather than
+    /// returning an error.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<MappedPng> {
+        let file = File::open(path)?;
+
+        // SAFETY: the mapping is read-only, and the caller guarantees the
+        // file stays unchanged while it is mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        Ok(MappedPng { mmap })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Checks the signature and iterates over the file's chunks.
+    pub fn chunks(&self) -> std::result::Result<ChunkRefs<'_>, PngError> {
+        ChunkRefs::new(&self.mmap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::png::Png;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_mapped_chunks() {
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"I am the first chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), b"I am the last chunk".to_vec()),
+        ]);
+        let path = std::env::temp_dir().join(format!("png_msg_mmap_{}.png", std::process::id()));
+        std::fs::write(&path, png.as_bytes()).unwrap();
+
+        // SAFETY: the file has a unique name and nothing else touches it
+        let mapped = unsafe { MappedPng::open(&path) }.unwrap();
+        let chunks: Vec<Chunk> = mapped
+            .chunks()
+            .unwrap()
+            .map(|chunk| chunk.unwrap().to_chunk())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(chunks.as_slice(), png.chunks());
+    }
+}
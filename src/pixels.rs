@@ -0,0 +1,359 @@
+use std::fmt::Display;
+use std::io::{Read, Write};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::adam7::{self, PASSES};
+use crate::filter;
+use crate::ihdr::{Ihdr, InterlaceMethod};
+
+/// Largest amount of data written into a single IDAT chunk by [`idat_chunks`].
+pub const IDAT_CHUNK_SIZE: usize = 8192;
+
+/// Number of bytes in one unfiltered scanline of `width` pixels, with
+/// sub-byte samples packed the way PNG stores them.
+pub fn stride(ihdr: &Ihdr, width: u32) -> usize {
+    let bits = width as u64 * ihdr.color_type.channels() as u64 * ihdr.bit_depth as u64;
+    bits.div_ceil(8) as usize
+}
+
+/// Distance in bytes between a byte and the matching byte of the previous
+/// pixel, which is what the scanline filters compare against.
+pub fn filter_bpp(ihdr: &Ihdr) -> usize {
+    (ihdr.color_type.channels() * ihdr.bit_depth as usize).div_ceil(8)
+}
+
+/// Size of the raw pixel buffer for this image: `height` rows of [`stride`]
+/// bytes without filter type bytes.
+pub fn image_size(ihdr: &Ihdr) -> Result<usize, PixelError> {
+    (stride(ihdr, ihdr.width) as u64)
+        .checked_mul(ihdr.height as u64)
+        .and_then(|size| usize::try_from(size).ok())
+        .ok_or(PixelError::ImageTooLarge)
+}
+
+/// Inflates and unfilters the concatenated IDAT data of an image into rows
+/// of raw samples. Adam7-interlaced images are reassembled into the same
+/// full-size buffer a non-interlaced image would give.
+pub fn decode(ihdr: &Ihdr, compressed: &[u8]) -> Result<Vec<u8>, PixelError> {
+    let size = image_size(ihdr)?;
+    let filtered = inflate(compressed, filtered_size(ihdr)?)?;
+    let bpp = filter_bpp(ihdr);
+
+    if ihdr.interlace_method == InterlaceMethod::None {
+        let mut pixels = Vec::with_capacity(size);
+        filter::unfilter(&filtered, stride(ihdr, ihdr.width), bpp, &mut pixels)?;
+        return Ok(pixels);
+    }
+
+    let mut pixels = vec![0; size];
+    let mut reduced = Vec::new();
+    let mut offset = 0;
+
+    for pass in &PASSES {
+        let (width, height) = pass.size(ihdr.width, ihdr.height);
+        if width == 0 || height == 0 {
+            continue;
+        }
+
+        let pass_stride = stride(ihdr, width);
+        let length = usize::try_from(scanlines_size(ihdr, width, height)?).map_err(|_| PixelError::ImageTooLarge)?;
+
+        reduced.clear();
+        filter::unfilter(&filtered[offset..offset + length], pass_stride, bpp, &mut reduced)?;
+        adam7::merge_pass(ihdr, pass, &reduced, &mut pixels);
+        offset += length;
+    }
+    Ok(pixels)
+}
+
+/// Filters and deflates rows of raw samples into the data of an image's IDAT
+/// chunks, splitting them into Adam7 passes first if the IHDR asks for it.
+pub fn encode(ihdr: &Ihdr, pixels: &[u8]) -> Result<Vec<u8>, PixelError> {
+    let expected = image_size(ihdr)?;
+    if pixels.len() != expected {
+        return Err(PixelError::BufferSize { expected, actual: pixels.len() });
+    }
+
+    let bpp = filter_bpp(ihdr);
+    let filter_type = adaptive_filter(ihdr);
+    let mut filtered = Vec::with_capacity(usize::try_from(filtered_size(ihdr)?).map_err(|_| PixelError::ImageTooLarge)?);
+
+    if ihdr.interlace_method == InterlaceMethod::None {
+        filter::filter(pixels, stride(ihdr, ihdr.width), bpp, filter_type, &mut filtered);
+    } else {
+        for pass in &PASSES {
+            let (width, height) = pass.size(ihdr.width, ihdr.height);
+            if width == 0 || height == 0 {
+                continue;
+            }
+
+            let reduced = adam7::extract_pass(ihdr, pass, pixels);
+            filter::filter(&reduced, stride(ihdr, width), bpp, filter_type, &mut filtered);
+        }
+    }
+    deflate(&filtered)
+}
+
+/// Splits a zlib stream into IDAT chunks of at most [`IDAT_CHUNK_SIZE`] bytes.
+pub fn idat_chunks(compressed: &[u8]) -> Vec<Chunk> {
+    compressed
+        .chunks(IDAT_CHUNK_SIZE)
+        .map(|data| Chunk::new(ChunkType::IDAT, data.to_vec()))
+        .collect()
+}
+
+/// Size of the inflated image data: every scanline plus its filter type
+/// byte, summed over the Adam7 passes for interlaced images.
+fn filtered_size(ihdr: &Ihdr) -> Result<u64, PixelError> {
+    match ihdr.interlace_method {
+        InterlaceMethod::None => scanlines_size(ihdr, ihdr.width, ihdr.height),
+        InterlaceMethod::Adam7 => PASSES.iter().try_fold(0u64, |total, pass| {
+            let (width, height) = pass.size(ihdr.width, ihdr.height);
+            total.checked_add(scanlines_size(ihdr, width, height)?).ok_or(PixelError::ImageTooLarge)
+        }),
+    }
+}
+
+/// Size of `height` filtered scanlines of `width` pixels, each with its
+/// filter type byte.
+fn scanlines_size(ihdr: &Ihdr, width: u32, height: u32) -> Result<u64, PixelError> {
+    if width == 0 || height == 0 {
+        return Ok(0);
+    }
+    (stride(ihdr, width) as u64 + 1)
+        .checked_mul(height as u64)
+        .ok_or(PixelError::ImageTooLarge)
+}
+
+/// The spec recommends no filtering for palette images and bit depths below
+/// eight, and the adaptive heuristic for everything else.
+pub(crate) fn adaptive_filter(ihdr: &Ihdr) -> Option<filter::FilterType> {
+    if ihdr.bit_depth < 8 || ihdr.color_type == crate::ihdr::ColorType::Indexed {
+        Some(filter::FilterType::None)
+    } else {
+        None
+    }
+}
+
+/// Inflates a zlib stream that should hold exactly `expected` bytes, without
+/// ever reading more than that so a hostile stream can't exhaust memory.
+pub(crate) fn inflate(compressed: &[u8], expected: u64) -> Result<Vec<u8>, PixelError> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take(expected + 1)
+        .read_to_end(&mut inflated)
+        .map_err(PixelError::Decompress)?;
+
+    if inflated.len() as u64 != expected {
+        return Err(PixelError::DataLength { expected, actual: inflated.len() as u64 });
+    }
+    Ok(inflated)
+}
+
+pub(crate) fn deflate(data: &[u8]) -> Result<Vec<u8>, PixelError> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).map_err(PixelError::Compress)?;
+    encoder.finish().map_err(PixelError::Compress)
+}
+
+#[derive(Debug)]
+pub enum PixelError {
+    MissingImageData,
+    ImageTooLarge,
+    Decompress(std::io::Error),
+    Compress(std::io::Error),
+    /// The inflated image data isn't the size the IHDR dimensions call for.
+    DataLength { expected: u64, actual: u64 },
+    NotEnoughData,
+    UnknownFilterType { row: usize, filter_type: u8 },
+    /// A pixel buffer handed in for encoding has the wrong size.
+    BufferSize { expected: usize, actual: usize },
+}
+
+impl std::error::Error for PixelError {}
+
+impl Display for PixelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PixelError::MissingImageData => write!(f, "The image has no IDAT chunks"),
+            PixelError::ImageTooLarge => write!(f, "The image dimensions are too large to decode"),
+            PixelError::Decompress(err) => write!(f, "Failed to inflate image data: {}", err),
+            PixelError::Compress(err) => write!(f, "Failed to deflate image data: {}", err),
+            PixelError::DataLength { expected, actual } => write!(
+                f,
+                "Expected {} bytes of filtered image data but found {}",
+                expected, actual
+            ),
+            PixelError::NotEnoughData => write!(f, "The image data ends in the middle of a scanline"),
+            PixelError::UnknownFilterType { row, filter_type } => {
+                write!(f, "Unknown filter type {} in scanline {}", filter_type, row)
+            }
+            PixelError::BufferSize { expected, actual } => write!(
+                f,
+                "Expected a pixel buffer of {} bytes but got {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::ColorType;
+
+    fn testing_ihdr(color_type: ColorType, bit_depth: u8) -> Ihdr {
+        Ihdr {
+            width: 7,
+            height: 5,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    fn interlaced(ihdr: Ihdr) -> Ihdr {
+        Ihdr { interlace_method: InterlaceMethod::Adam7, ..ihdr }
+    }
+
+    fn testing_pixels(ihdr: &Ihdr) -> Vec<u8> {
+        let size = image_size(ihdr).unwrap();
+        let mut pixels: Vec<u8> = (0..size as u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+
+        // Interlacing only carries real pixels, so zero the padding bits at
+        // the end of each row for the round trips to compare equal.
+        let used_bits = ihdr.width as usize * ihdr.color_type.channels() * ihdr.bit_depth as usize % 8;
+        if used_bits != 0 {
+            let row_stride = stride(ihdr, ihdr.width);
+            for row in pixels.chunks_mut(row_stride) {
+                row[row_stride - 1] &= !(0xFF >> used_bits);
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn test_stride() {
+        assert_eq!(stride(&testing_ihdr(ColorType::Grayscale, 1), 7), 1);
+        assert_eq!(stride(&testing_ihdr(ColorType::Indexed, 4), 7), 4);
+        assert_eq!(stride(&testing_ihdr(ColorType::Rgb, 16), 7), 42);
+        assert_eq!(stride(&testing_ihdr(ColorType::Rgba, 8), 7), 28);
+    }
+
+    #[test]
+    fn test_round_trip_every_color_type_and_bit_depth() {
+        for color_type in [ColorType::Grayscale, ColorType::Rgb, ColorType::Indexed, ColorType::GrayscaleAlpha, ColorType::Rgba] {
+            for &bit_depth in color_type.allowed_bit_depths() {
+                let ihdr = testing_ihdr(color_type, bit_depth);
+                let pixels = testing_pixels(&ihdr);
+
+                let compressed = encode(&ihdr, &pixels).unwrap();
+                let decoded = decode(&ihdr, &compressed).unwrap();
+
+                assert_eq!(decoded, pixels, "{} at bit depth {}", color_type, bit_depth);
+            }
+        }
+    }
+
+    #[test]
+    fn test_interlaced_round_trip_every_color_type_and_bit_depth() {
+        for color_type in [ColorType::Grayscale, ColorType::Rgb, ColorType::Indexed, ColorType::GrayscaleAlpha, ColorType::Rgba] {
+            for &bit_depth in color_type.allowed_bit_depths() {
+                let ihdr = interlaced(testing_ihdr(color_type, bit_depth));
+                let pixels = testing_pixels(&ihdr);
+
+                let compressed = encode(&ihdr, &pixels).unwrap();
+                let decoded = decode(&ihdr, &compressed).unwrap();
+
+                assert_eq!(decoded, pixels, "{} at bit depth {}", color_type, bit_depth);
+            }
+        }
+    }
+
+    #[test]
+    fn test_interlaced_tiny_images() {
+        for (width, height) in [(1, 1), (2, 1), (1, 9), (9, 2)] {
+            let ihdr = Ihdr { width, height, ..interlaced(testing_ihdr(ColorType::Grayscale, 1)) };
+            let pixels = testing_pixels(&ihdr);
+
+            let compressed = encode(&ihdr, &pixels).unwrap();
+            assert_eq!(decode(&ihdr, &compressed).unwrap(), pixels, "{}x{}", width, height);
+        }
+    }
+
+    #[test]
+    fn test_decode_interlaced_known_passes() {
+        // A 5x3 grayscale image whose pixels are numbered 0 to 14, with every
+        // pass scanline left unfiltered. Pass 3 has no rows at this height.
+        #[rustfmt::skip]
+        let filtered = [
+            0, 0,
+            0, 4,
+            0, 2,
+            0, 10, 12, 14,
+            0, 1, 3,
+            0, 11, 13,
+            0, 5, 6, 7, 8, 9,
+        ];
+        let ihdr = Ihdr { width: 5, height: 3, ..interlaced(testing_ihdr(ColorType::Grayscale, 8)) };
+
+        let pixels = decode(&ihdr, &deflate(&filtered).unwrap()).unwrap();
+        assert_eq!(pixels, (0..15).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_interlaced_and_plain_encode_same_pixels() {
+        let plain = testing_ihdr(ColorType::Rgba, 8);
+        let pixels = testing_pixels(&plain);
+
+        let compressed = encode(&interlaced(plain), &pixels).unwrap();
+        assert!(decode(&plain, &compressed).is_err());
+        assert_eq!(decode(&interlaced(plain), &compressed).unwrap(), pixels);
+    }
+
+    #[test]
+    fn test_encode_wrong_buffer_size() {
+        let ihdr = testing_ihdr(ColorType::Rgb, 8);
+        let result = encode(&ihdr, &[0; 10]);
+        assert!(matches!(result, Err(PixelError::BufferSize { expected: 105, actual: 10 })));
+    }
+
+    #[test]
+    fn test_decode_too_much_data() {
+        let ihdr = testing_ihdr(ColorType::Grayscale, 8);
+        let compressed = deflate(&[0; 1000]).unwrap();
+        let result = decode(&ihdr, &compressed);
+        assert!(matches!(result, Err(PixelError::DataLength { expected: 40, actual: 41 })));
+    }
+
+    #[test]
+    fn test_decode_corrupt_stream() {
+        let ihdr = testing_ihdr(ColorType::Grayscale, 8);
+        let result = decode(&ihdr, &[1, 2, 3, 4]);
+        assert!(matches!(result, Err(PixelError::Decompress(_))));
+    }
+
+    #[test]
+    fn test_huge_dimensions_are_too_large() {
+        // the raw size still fits in a u64, the size with filter bytes doesn't
+        let ihdr = Ihdr { width: 1073741825, height: 2147483646, ..testing_ihdr(ColorType::Rgba, 16) };
+        assert!(image_size(&ihdr).is_ok());
+
+        let compressed = deflate(&[0; 16]).unwrap();
+        assert!(matches!(decode(&ihdr, &compressed), Err(PixelError::ImageTooLarge)));
+        assert!(matches!(decode(&interlaced(ihdr), &compressed), Err(PixelError::ImageTooLarge)));
+    }
+
+    #[test]
+    fn test_idat_chunks() {
+        let chunks = idat_chunks(&vec![7; IDAT_CHUNK_SIZE * 2 + 1]);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| *c.chunk_type() == ChunkType::IDAT));
+        assert_eq!(chunks[2].length(), 1);
+    }
+}
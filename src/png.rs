@@ -1,77 +1,315 @@
+use crate::apng::{self, Frames};
 use crate::chunk::Chunk;
-use crate::{Result,Error};
-use std::{convert::TryFrom,fmt::Display};
+use crate::chunk::ChunkError;
+use crate::chunk_ref::ChunkRefs;
+use crate::chunk_type::{ChunkType, MatchesChunkType};
+use crate::ihdr::{ColorType, Ihdr};
+use crate::pixels::{self, PixelError};
+use crate::registry::{self, SampleEdit};
+use crate::signature::Signature;
+use crate::structure::{self, StructureError};
+use crate::writer::PngWriter;
+use crate::Result;
+use std::{convert::TryFrom,fmt::Display,io::Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+#[derive(Debug)]
 pub struct Png {
     chunks: Vec<Chunk>
 }
 
 impl Png  {
-    const STANDARD_HEADER: [u8;8]= [137, 80, 78, 71, 13, 10, 26, 10];
+    pub const STANDARD_HEADER: [u8;8]= [137, 80, 78, 71, 13, 10, 26, 10];
 
-    fn from_chunks(chunks: Vec<Chunk>) -> Png {
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
         Self {chunks}
     }
 
-    fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk)
+    /// Adds a chunk at the end of the file. When the file already ends with
+    /// IEND the chunk goes right before it, so IEND stays last. Fails like
+    /// [`Png::insert_chunk_at`] if that breaks the ordering rules.
+    pub fn append_chunk(&mut self, chunk: Chunk) -> Result<()> {
+        let ends_with_iend = self.chunks.last().is_some_and(|c| *c.chunk_type() == ChunkType::IEND);
+
+        if ends_with_iend && *chunk.chunk_type() != ChunkType::IEND {
+            self.insert_chunk_at(self.chunks.len() - 1, chunk)
+        } else {
+            self.insert_chunk_at(self.chunks.len(), chunk)
+        }
     }
 
-    fn remove_chunk(&mut self,chunk_type: &str) -> Result<Chunk> {
-        let index = self.chunks.iter().position(|c| (*c).chunk_type().to_string() == chunk_type).ok_or(PngError::UnknownChunkType)?;
-        let removed = self.chunks.remove(index);
-        Ok(removed)
+    /// Removes the first chunk of the given type, unless the file would then
+    /// break an ordering rule it didn't break before.
+    pub fn remove_chunk<T: MatchesChunkType>(&mut self,chunk_type: T) -> Result<Chunk> {
+        let index = self.position(chunk_type).ok_or(PngError::UnknownChunkType)?;
+        let removed: Vec<bool> = (0..self.chunks.len()).map(|i| i == index).collect();
+
+        self.check_removal(&removed)?;
+        Ok(self.chunks.remove(index))
     }   
 
-    fn header(&self) -> &[u8;8] {
+    /// Removes every chunk of the given type and returns them in file order.
+    /// Nothing is removed if that would break the ordering rules.
+    pub fn remove_all<T: MatchesChunkType>(&mut self, chunk_type: T) -> Result<Vec<Chunk>> {
+        let removed: Vec<bool> = self.chunks.iter().map(|c| chunk_type.matches_chunk_type(c.chunk_type())).collect();
+        self.check_removal(&removed)?;
+
+        let (removed, kept) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|c| chunk_type.matches_chunk_type(c.chunk_type()));
+        self.chunks = kept;
+        Ok(removed)
+    }
+
+    /// Keeps only the chunks for which `keep` returns true. Nothing is
+    /// removed if that would break the ordering rules.
+    pub fn retain<F: FnMut(&Chunk) -> bool>(&mut self, mut keep: F) -> Result<()> {
+        let removed: Vec<bool> = self.chunks.iter().map(|c| !keep(c)).collect();
+        self.check_removal(&removed)?;
+
+        let mut removed = removed.into_iter();
+        self.chunks.retain(|_| !removed.next().unwrap_or(false));
+        Ok(())
+    }
+
+    /// Replaces the data of every chunk for which `f` returns new data, in
+    /// file order. Chunk types stay as they are, so the order can't change.
+    pub(crate) fn map_chunk_data<F: FnMut(&Chunk) -> Option<Vec<u8>>>(&mut self, mut f: F) {
+        for chunk in self.chunks.iter_mut() {
+            if let Some(data) = f(chunk) {
+                *chunk = Chunk::new(chunk.chunk_type().clone(), data);
+            }
+        }
+    }
+
+    /// Inserts a chunk so that it ends up at `index`, as long as the
+    /// resulting file still follows the PNG ordering rules.
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        if index > self.chunks.len() {
+            return Err(PngError::IndexOutOfRange(index).into());
+        }
+
+        self.check_order(index, chunk.chunk_type(), false)?;
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    /// Inserts a chunk right before the first chunk of the given type.
+    pub fn insert_before<T: MatchesChunkType>(&mut self, chunk_type: T, chunk: Chunk) -> Result<()> {
+        let index = self.position(chunk_type).ok_or(PngError::UnknownChunkType)?;
+        self.insert_chunk_at(index, chunk)
+    }
+
+    /// Inserts a chunk right after the first chunk of the given type.
+    pub fn insert_after<T: MatchesChunkType>(&mut self, chunk_type: T, chunk: Chunk) -> Result<()> {
+        let index = self.position(chunk_type).ok_or(PngError::UnknownChunkType)?;
+        self.insert_chunk_at(index + 1, chunk)
+    }
+
+    /// Swaps the first chunk of the given type for `chunk` and returns the
+    /// chunk that was replaced.
+    pub fn replace_chunk<T: MatchesChunkType>(&mut self, chunk_type: T, chunk: Chunk) -> Result<Chunk> {
+        let index = self.position(chunk_type).ok_or(PngError::UnknownChunkType)?;
+
+        self.check_order(index, chunk.chunk_type(), true)?;
+        Ok(std::mem::replace(&mut self.chunks[index], chunk))
+    }
+
+    fn position<T: MatchesChunkType>(&self, chunk_type: T) -> Option<usize> {
+        self.chunks.iter().position(|c| chunk_type.matches_chunk_type(c.chunk_type()))
+    }
+
+    /// Checks the chunk order for putting a chunk of type `chunk_type` at
+    /// `index`, either in place of the chunk there or before it. Problems the
+    /// file already had are not reported, see [`structure::check_change`].
+    fn check_order(&self, index: usize, chunk_type: &ChunkType, replace: bool) -> std::result::Result<(), StructureError> {
+        let chunk_types: Vec<&ChunkType> = self.chunks.iter().map(Chunk::chunk_type).collect();
+
+        // in an APNG, only frame chunks may go between an fcTL and its data,
+        // or take the place of a frame chunk
+        let splits_frame = match replace {
+            true => apng::is_frame_chunk(chunk_types[index]),
+            false => apng::splits_frame(&chunk_types, index),
+        };
+        if !apng::is_frame_chunk(chunk_type) && splits_frame {
+            return Err(StructureError::SplitsFrame { index });
+        }
+
+        structure::check_change(&chunk_types, index, chunk_type, replace)
+    }
+
+    /// Checks removing the chunks marked in `removed`, see
+    /// [`structure::check_removal`].
+    fn check_removal(&self, removed: &[bool]) -> std::result::Result<(), StructureError> {
+        let chunk_types: Vec<&ChunkType> = self.chunks.iter().map(Chunk::chunk_type).collect();
+        structure::check_removal(&chunk_types, removed)
+    }
+
+    pub fn header(&self) -> &[u8;8] {
         &Png::STANDARD_HEADER
     } 
 
-    fn chunks(&self) -> &[Chunk] {
+    pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
 
-    fn chunk_by_type(&self,chunk_type: &str) -> Option<&Chunk> {
-        self.chunks.iter().find(|&c| c.chunk_type().to_string() == chunk_type)
+    pub fn chunk_by_type<T: MatchesChunkType>(&self,chunk_type: T) -> Option<&Chunk> {
+        self.chunks_by_type(chunk_type).next()
+    }
+
+    /// Iterates over every chunk of the given type in file order.
+    pub fn chunks_by_type<T: MatchesChunkType>(&self, chunk_type: T) -> impl Iterator<Item = &Chunk> {
+        self.chunks.iter().filter(move |c| chunk_type.matches_chunk_type(c.chunk_type()))
     }       
 
-    fn as_bytes(&self) -> Vec<u8> {
-        let header: Vec<u8> = self.header().iter().copied().collect();
-        let body: Vec<u8> = self.chunks.iter().flat_map(|c| c.as_bytes().into_iter()).collect();
+    /// Parses the IHDR chunk, which the spec requires to be the first chunk.
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        let first = self.chunks.first().ok_or(StructureError::MissingChunk(ChunkType::IHDR))?;
 
-        header.into_iter().chain(body.into_iter()).collect()
+        if *first.chunk_type() != ChunkType::IHDR {
+            return Err(StructureError::FirstChunkNotIhdr(first.chunk_type().clone()).into());
+        }
+        Ok(Ihdr::try_from(first.data())?)
+    }
 
+    /// Decodes the image data into rows of raw samples, `height` rows of
+    /// [`pixels::stride`] bytes each.
+    pub fn pixels(&self) -> Result<Vec<u8>> {
+        let ihdr = self.ihdr()?;
+        let mut idats = self.chunks_by_type(&ChunkType::IDAT).peekable();
+        if idats.peek().is_none() {
+            return Err(PixelError::MissingImageData.into());
+        }
+
+        let compressed: Vec<u8> = idats.flat_map(|chunk| chunk.data().iter().copied()).collect();
+        Ok(pixels::decode(&ihdr, &compressed)?)
     }
-}
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
+    /// Re-encodes the image data from rows of raw samples, replacing every
+    /// IDAT chunk with freshly compressed ones in the same spot.
+    ///
+    /// Changing the image can leave other chunks describing pixels that no
+    /// longer exist, so this also applies [`Png::apply_sample_edit`] and
+    /// returns the chunks it dropped.
+    pub fn set_pixels(&mut self, data: &[u8]) -> Result<Vec<Chunk>> {
+        let ihdr = self.ihdr()?;
+        let idats = pixels::idat_chunks(&pixels::encode(&ihdr, data)?);
 
-    fn try_from(value: &[u8]) -> Result<Self> {
-        if value.len() < Png::STANDARD_HEADER.len() {
-            return Err(Box::from(PngError::TooSmall));
-        }
+        let index = match self.position(&ChunkType::IDAT) {
+            Some(index) => index,
+            None => self.position(&ChunkType::IEND).unwrap_or(self.chunks.len()),
+        };
+        self.chunks.retain(|c| *c.chunk_type() != ChunkType::IDAT);
+        self.chunks.splice(index..index, idats);
+        Ok(self.apply_sample_edit(SystemTime::now()))
+    }
 
-        let mut index = 0;
+    /// Brings the other chunks in line with image data whose sample values
+    /// changed while the header and palette stayed the same, as after
+    /// [`Png::set_pixels`]. Chunks failing [`survives_sample_edit`] are
+    /// dropped and returned in file order, and tIME is set to `now`.
+    ///
+    /// Only [`Png::set_pixels`] calls this. Code that rewrites IDAT some other
+    /// way, like streaming chunks through a [`PngWriter`], has to filter them
+    /// with [`survives_sample_edit`] itself.
+    pub fn apply_sample_edit(&mut self, now: SystemTime) -> Vec<Chunk> {
+        let color_type = self.ihdr().map_or(ColorType::Grayscale, |ihdr| ihdr.color_type);
+        let (kept, dropped): (Vec<Chunk>, _) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|chunk| survives_sample_edit(chunk, color_type));
+        self.chunks = kept
+            .into_iter()
+            .map(|chunk| if *chunk.chunk_type() == ChunkType::TIME { time_chunk(now) } else { chunk })
+            .collect();
+        dropped
+    }
 
-        // validate header
-        let header = &value[index..index + Png::STANDARD_HEADER.len()];
-        index += Png::STANDARD_HEADER.len();
+    /// Iterates over the frames of an animated PNG, see [`apng::validate`]
+    /// for checking that they are consistent.
+    pub fn frames(&self) -> Frames<'_> {
+        Frames::new(&self.chunks)
+    }
 
-        if Png::STANDARD_HEADER != header {
-            return Err(Box::from(PngError::InvalidHeader));
-        }
+    /// Checks the chunk ordering rules of the PNG spec, then the APNG rules,
+    /// see [`apng::validate`].
+    pub fn validate(&self) -> Result<()> {
+        structure::validate(self.chunks.iter().map(Chunk::chunk_type))?;
+        apng::validate(self)?;
+        Ok(())
+    }
 
-        // parse one chunk at a time
-        let mut chunks = Vec::new();
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let header: Vec<u8> = self.header().to_vec();
+        let body: Vec<u8> = self.chunks.iter().flat_map(|c| c.as_bytes().into_iter()).collect();
 
-        while index < value.len() {
-            let bytes = &value[index..];
-            let chunk = Chunk::try_from(bytes)?;
-            index += chunk.length() + Chunk::METADATA_BYTES;
+        header.into_iter().chain(body).collect()
 
-            chunks.push(chunk);
+    }
+
+    /// Streams the file to `writer` instead of building it in memory first.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        let mut writer = PngWriter::new(writer)?;
+        for chunk in &self.chunks {
+            writer.write_chunk(chunk)?;
         }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Whether `chunk` stays valid when an image of `color_type` gets new sample
+/// values but keeps its header and palette. This is the PNG safe-to-copy
+/// rule, except that registered types follow their
+/// [`SampleEdit`](registry::SampleEdit) and signatures that don't cover the
+/// image data are kept.
+pub fn survives_sample_edit(chunk: &Chunk, color_type: ColorType) -> bool {
+    let chunk_type = chunk.chunk_type();
+    if chunk_type.is_critical() || chunk_type.is_safe_to_copy() {
+        return true;
+    }
+    if let Some(info) = registry::lookup(chunk_type) {
+        return match info.sample_edit {
+            SampleEdit::Keep | SampleEdit::Touch => true,
+            SampleEdit::Drop => false,
+            SampleEdit::KeepForPalette => color_type == ColorType::Indexed,
+        };
+    }
+
+    // signatures are our own, so we know which ones the edit invalidates
+    *chunk_type == ChunkType::SIGNATURE
+        && Signature::try_from(chunk.data()).is_ok_and(|signature| !signature.covers_image)
+}
+
+/// A tIME chunk holding `time` in UTC.
+fn time_chunk(time: SystemTime) -> Chunk {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // days since 1970-01-01 to a civil date, after Howard Hinnant's
+    // civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    let mut data = Vec::with_capacity(7);
+    data.extend_from_slice(&u16::try_from(year).unwrap_or(u16::MAX).to_be_bytes());
+    data.extend_from_slice(&[month, day, seconds / 3600, seconds / 60 % 60, seconds % 60].map(|field| field as u8));
+    Chunk::new(ChunkType::TIME, data)
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        let chunks = ChunkRefs::new(value)?
+            .map(|chunk| chunk.map(|chunk| chunk.to_chunk()))
+            .collect::<std::result::Result<_, _>>()?;
 
         Ok(Png { chunks })
     }
@@ -82,16 +320,35 @@ pub enum PngError {
     InvalidHeader,
     TooSmall,
     UnknownChunkType,
+    IndexOutOfRange(usize),
+    /// A chunk failed to parse. `index` counts chunks from zero and `offset`
+    /// is the byte position of the chunk's length field within the file.
+    InvalidChunk {
+        index: usize,
+        offset: usize,
+        source: ChunkError,
+    },
 }
 
-impl std::error::Error for PngError {}
+impl std::error::Error for PngError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PngError::InvalidChunk { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
 
 impl Display for PngError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
+        match self {
             PngError::InvalidHeader => write!(f, "Invalid header"),
             PngError::TooSmall => write!(f, "The given source is too small to be a valid PNG file"),
             PngError::UnknownChunkType => write!(f, "Unknown chunk type"),
+            PngError::IndexOutOfRange(index) => write!(f, "Chunk index {} is out of range", index),
+            PngError::InvalidChunk { index, offset, source } => {
+                write!(f, "Chunk #{} at offset {:#X}: {}", index, offset, source)
+            }
         }
     }
 }
@@ -100,9 +357,11 @@ mod tests {
     use super::*;
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
+    use crate::Error;
     use std::convert::TryFrom;
     use std::str::FromStr;
 
+    #[allow(clippy::vec_init_then_push)]
     fn testing_chunks() -> Vec<Chunk> {
         let mut chunks = Vec::with_capacity(3);
 
@@ -191,6 +450,52 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_invalid_chunk_reports_position() {
+        let chunks = testing_chunks();
+        let first_chunk_len = chunks[0].as_bytes().len();
+        let mut bytes: Vec<u8> = Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(chunks.into_iter().flat_map(|chunk| chunk.as_bytes()))
+            .collect();
+
+        // corrupt the last CRC byte of the second chunk
+        let second_chunk_end = Png::STANDARD_HEADER.len() + first_chunk_len
+            + Chunk::METADATA_BYTES + "I am another chunk".len();
+        bytes[second_chunk_end - 1] ^= 0xFF;
+
+        let err = Png::try_from(bytes.as_ref()).unwrap_err();
+
+        match err {
+            PngError::InvalidChunk { index, offset, source: ChunkError::InvalidCrc(..) } => {
+                assert_eq!(index, 1);
+                assert_eq!(offset, Png::STANDARD_HEADER.len() + first_chunk_len);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_truncated_png_does_not_panic() {
+        for len in 0..PNG_FILE.len() {
+            let _ = Png::try_from(&PNG_FILE[..len]);
+        }
+    }
+
+    #[test]
+    fn test_truncated_png_reports_chunk() {
+        // cuts the file in the middle of the IDAT chunk
+        let png = Png::try_from(&PNG_FILE[..1000]);
+
+        match png {
+            Err(PngError::InvalidChunk { index: 4, source: ChunkError::Truncated { declared, .. }, .. }) => {
+                assert_eq!(declared, 4681);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -209,21 +514,337 @@ mod tests {
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         let chunk = png.chunk_by_type("TeSt").unwrap();
         assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_append_chunk_before_iend() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap()).unwrap();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+
+        let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, ["FrSt", "miDl", "LASt", "TeSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_png_from_image_file_is_valid() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.validate().is_ok());
+    }
+
+    fn valid_png() -> Png {
+        Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "image data").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ])
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|c| c.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_insert_chunk_at() {
+        let mut png = valid_png();
+        png.insert_chunk_at(1, chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "TeSt", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_insert_chunk_at_out_of_range() {
+        let mut png = valid_png();
+        let result = png.insert_chunk_at(4, chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(matches!(result, Err(Error::Png(PngError::IndexOutOfRange(4)))));
+    }
+
+    #[test]
+    fn test_insert_chunk_breaking_order() {
+        let mut png = valid_png();
+
+        let result = png.insert_chunk_at(0, chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(matches!(result, Err(Error::Structure(StructureError::FirstChunkNotIhdr(_)))));
+
+        let result = png.insert_after("IEND", chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(matches!(result, Err(Error::Structure(StructureError::ChunkAfterIend { .. }))));
+
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_insert_into_file_with_unrelated_problem() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "image data").unwrap(),
+            chunk_from_strings("ruSt", "Splits the image data").unwrap(),
+            chunk_from_strings("IDAT", "stray image data").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        png.insert_before("IEND", chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+
+        assert!(matches!(png.validate(), Err(Error::Structure(StructureError::IdatNotConsecutive { index: 3 }))));
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "ruSt", "IDAT", "TeSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_insert_misplaced_ancillary_chunk() {
+        let mut png = valid_png();
+        png.insert_after("IHDR", chunk_from_strings("PLTE", "palette").unwrap()).unwrap();
+
+        let result = png.insert_after("PLTE", Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0, 0, 177, 143]));
+        assert!(matches!(result, Err(Error::Structure(StructureError::Misplaced { index: 2, .. }))));
+        png.insert_before("PLTE", Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0, 0, 177, 143])).unwrap();
+    }
+
+    #[test]
+    fn test_insert_before_and_after() {
+        let mut png = valid_png();
+        png.insert_after("IHDR", chunk_from_strings("AfTr", "after").unwrap()).unwrap();
+        png.insert_before("IEND", chunk_from_strings("BeFr", "before").unwrap()).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "AfTr", "IDAT", "BeFr", "IEND"]);
+    }
+
+    #[test]
+    fn test_insert_before_unknown_type() {
+        let mut png = valid_png();
+        let result = png.insert_before("TeSt", chunk_from_strings("BeFr", "before").unwrap());
+        assert!(matches!(result, Err(Error::Png(PngError::UnknownChunkType))));
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = valid_png();
+        png.insert_after("IHDR", chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+
+        let old = png.replace_chunk("TeSt", chunk_from_strings("TeSt", "Updated").unwrap()).unwrap();
+
+        assert_eq!(old.data_as_string().unwrap(), "Message");
+        assert_eq!(png.chunk_by_type("TeSt").unwrap().data_as_string().unwrap(), "Updated");
+    }
+
+    #[test]
+    fn test_replace_chunk_breaking_order() {
+        let mut png = valid_png();
+        let result = png.replace_chunk("IHDR", chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(matches!(result, Err(Error::Structure(_))));
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_chunk_by_chunk_type() {
+        let png = testing_png();
+        let chunk_type = ChunkType::from_str("miDl").unwrap();
+        let chunk = png.chunk_by_type(&chunk_type).unwrap();
+        assert_eq!(&chunk.data_as_string().unwrap(), "I am another chunk");
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "First").unwrap()).unwrap();
+        png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap()).unwrap();
+
+        let messages: Vec<String> = png
+            .chunks_by_type("TeSt")
+            .map(|c| c.data_as_string().unwrap())
+            .collect();
+        assert_eq!(messages, ["First", "Second"]);
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "First").unwrap()).unwrap();
+        png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap()).unwrap();
+
+        let removed = png.remove_all(ChunkType::from_str("TeSt").unwrap()).unwrap();
+
+        assert_eq!(removed.len(), 2);
+        assert_eq!(&removed[1].data_as_string().unwrap(), "Second");
+        assert_eq!(chunk_types(&png), ["FrSt", "miDl", "LASt"]);
+        assert!(png.remove_all("TeSt").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut png = testing_png();
+        png.retain(|c| c.chunk_type().is_critical()).unwrap();
+        assert_eq!(chunk_types(&png), ["FrSt", "LASt"]);
+    }
+
+    #[test]
+    fn test_ihdr() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.ihdr().unwrap();
+
+        assert_eq!((ihdr.width, ihdr.height), (50, 50));
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, crate::ihdr::ColorType::Rgba);
+    }
+
+    #[test]
+    fn test_ihdr_missing() {
+        let png = testing_png();
+        assert!(matches!(png.ihdr(), Err(Error::Structure(StructureError::FirstChunkNotIhdr(_)))));
+    }
+
+    #[test]
+    fn test_pixels() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let pixels = png.pixels().unwrap();
+
+        assert_eq!(pixels.len(), 50 * 50 * 4);
+        assert_eq!(crc::crc32::checksum_ieee(&pixels), 3827446951);
+        assert_eq!(pixels[25 * 200 + 100..25 * 200 + 108], [240, 240, 240, 255, 220, 177, 177, 237]);
+    }
+
+    #[test]
+    fn test_set_pixels_round_trip() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut pixels = png.pixels().unwrap();
+        pixels[0] ^= 0xFF;
+
+        png.set_pixels(&pixels).unwrap();
+
+        assert_eq!(png.pixels().unwrap(), pixels);
+        assert!(png.validate().is_ok());
+        assert_eq!(png.chunks().last().unwrap().chunk_type().to_string(), "IEND");
+    }
+
+    #[test]
+    fn test_interlaced_pixels_round_trip() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let pixels = png.pixels().unwrap();
+
+        let mut ihdr = png.ihdr().unwrap();
+        ihdr.interlace_method = crate::ihdr::InterlaceMethod::Adam7;
+        png.replace_chunk("IHDR", ihdr.to_chunk()).unwrap();
+        png.set_pixels(&pixels).unwrap();
+
+        let reparsed = Png::try_from(png.as_bytes().as_ref()).unwrap();
+        assert_eq!(reparsed.pixels().unwrap(), pixels);
+    }
+
+    #[test]
+    fn test_set_pixels_drops_unsafe_to_copy() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("ruST", "Describes the old pixels").unwrap()).unwrap();
+        png.append_chunk(chunk_from_strings("ruSt", "Safe to copy").unwrap()).unwrap();
+        png.append_chunk(Chunk::new(ChunkType::from_str("tIME").unwrap(), vec![7, 234, 10, 16, 9, 5, 0])).unwrap();
+
+        let pixels = png.pixels().unwrap();
+        let dropped = png.set_pixels(&pixels).unwrap();
+
+        let dropped: Vec<String> = dropped.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(dropped, ["ruST"]);
+        assert!(png.chunk_by_type("ruSt").is_some());
+        assert!(png.chunk_by_type("ruST").is_none());
+        assert_ne!(png.chunk_by_type("tIME").unwrap().data(), [7, 234, 10, 16, 9, 5, 0]);
+    }
+
+    #[test]
+    fn test_time_chunk() {
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(1_792_141_500);
+        assert_eq!(time_chunk(time).data(), [0x07, 0xea, 10, 16, 9, 5, 0]);
+        let leap_day = UNIX_EPOCH + std::time::Duration::from_secs(951_868_799);
+        assert_eq!(time_chunk(leap_day).data(), [0x07, 0xd0, 2, 29, 23, 59, 59]);
+        assert_eq!(time_chunk(UNIX_EPOCH).data(), [0x07, 0xb2, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_set_pixels_drops_sample_dependent_chunks() {
+        let ihdr = Ihdr {
+            width: 2,
+            height: 2,
+            bit_depth: 8,
+            color_type: crate::ihdr::ColorType::Indexed,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: crate::ihdr::InterlaceMethod::None,
+        };
+        let pixels = [0, 1, 1, 1];
+        let mut chunks = vec![
+            ihdr.to_chunk(),
+            Chunk::new(ChunkType::from_str("sBIT").unwrap(), vec![5, 6, 5]),
+            Chunk::new(ChunkType::from_str("PLTE").unwrap(), vec![0, 0, 0, 255, 255, 255]),
+            Chunk::new(ChunkType::from_str("tRNS").unwrap(), vec![0]),
+            Chunk::new(ChunkType::from_str("hIST").unwrap(), vec![0, 1, 0, 3]),
+        ];
+        chunks.extend(pixels::idat_chunks(&pixels::encode(&ihdr, &pixels).unwrap()));
+        chunks.push(Chunk::new(ChunkType::IEND, vec![]));
+        let mut png = Png::from_chunks(chunks);
+
+        let dropped = png.set_pixels(&[1, 1, 1, 0]).unwrap();
+
+        let dropped: Vec<String> = dropped.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(dropped, ["sBIT", "hIST"]);
+        assert!(png.chunk_by_type("PLTE").is_some());
+        assert!(png.chunk_by_type("tRNS").is_some());
+        assert_eq!(png.pixels().unwrap(), [1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_set_pixels_drops_colour_key_transparency() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut ihdr = png.ihdr().unwrap();
+        ihdr.color_type = ColorType::Rgb;
+        png.replace_chunk("IHDR", ihdr.to_chunk()).unwrap();
+        let pixels = vec![0; 50 * 50 * 3];
+        png.set_pixels(&pixels).unwrap();
+        png.insert_after("IHDR", Chunk::new(ChunkType::from_str("tRNS").unwrap(), vec![0, 0, 0, 0, 0, 0])).unwrap();
+
+        let dropped = png.set_pixels(&pixels).unwrap();
+
+        let dropped: Vec<String> = dropped.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(dropped, ["tRNS"]);
+    }
+
+    #[test]
+    fn test_set_pixels_wrong_size() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let result = png.set_pixels(&[0; 10]);
+        assert!(matches!(result, Err(Error::Pixel(PixelError::BufferSize { .. }))));
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         png.remove_chunk("TeSt").unwrap();
         let chunk = png.chunk_by_type("TeSt");
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_breaking_order() {
+        let mut png = valid_png();
+
+        let result = png.remove_chunk("IHDR");
+        assert!(matches!(result, Err(Error::Structure(StructureError::FirstChunkNotIhdr(_)))));
+        let result = png.remove_all("IEND");
+        assert!(matches!(result, Err(Error::Structure(StructureError::MissingChunk(_)))));
+        let result = png.retain(|c| *c.chunk_type() != ChunkType::IDAT);
+        assert!(matches!(result, Err(Error::Structure(StructureError::MissingChunk(_)))));
+
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_append_breaking_order() {
+        let mut png = valid_png();
+
+        let result = png.append_chunk(chunk_from_strings("PLTE", "palette").unwrap());
+        assert!(matches!(result, Err(Error::Structure(StructureError::PlteAfterIdat { index: 2 }))));
+        let result = png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        assert!(matches!(result, Err(Error::Structure(StructureError::ChunkAfterIend { index: 3, .. }))));
+
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "IEND"]);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -231,6 +852,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::iter_cloned_collect)]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
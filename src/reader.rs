@@ -0,0 +1,199 @@
+use std::io::{ErrorKind, Read};
+use crate::chunk::{Chunk, ChunkError};
+use crate::chunk_type::ChunkType;
+use crate::png::{Png, PngError};
+use crate::Result;
+
+/// Reads a PNG from any `Read` source one chunk at a time.
+///
+/// The signature is checked up front by [`PngReader::new`]; after that the
+/// reader is an iterator of chunks, so only the chunk currently being parsed
+/// is held in memory.
+pub struct PngReader<R: Read> {
+    inner: R,
+    offset: usize,
+    index: usize,
+    done: bool,
+}
+
+impl<R: Read> PngReader<R> {
+    pub fn new(mut inner: R) -> Result<PngReader<R>> {
+        let mut header = [0; Png::STANDARD_HEADER.len()];
+        inner.read_exact(&mut header).map_err(|err| match err.kind() {
+            ErrorKind::UnexpectedEof => PngError::TooSmall.into(),
+            _ => crate::Error::from(err),
+        })?;
+
+        if header != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidHeader.into());
+        }
+
+        Ok(PngReader {
+            inner,
+            offset: Png::STANDARD_HEADER.len(),
+            index: 0,
+            done: false,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        let mut prefix = [0; Chunk::DATA_LENGTH_BYTES + Chunk::CHUNK_TYPE_BYTES];
+        let read = read_up_to(&mut self.inner, &mut prefix)?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < prefix.len() {
+            return Err(self.invalid_chunk(ChunkError::InputTooSmall));
+        }
+
+        let (data_length, chunk_type_bytes) = prefix.split_at(Chunk::DATA_LENGTH_BYTES);
+        let data_length = u32::from_be_bytes(data_length.try_into().expect("4 byte length")) as usize;
+
+        if data_length > Chunk::MAX_DATA_LENGTH {
+            return Err(self.invalid_chunk(ChunkError::LengthOutOfRange(data_length)));
+        }
+
+        let chunk_type_bytes: [u8; 4] = chunk_type_bytes.try_into().expect("4 byte chunk type");
+        let chunk_type = match ChunkType::try_from(chunk_type_bytes) {
+            Ok(chunk_type) if chunk_type.is_valid() => chunk_type,
+            _ => return Err(self.invalid_chunk(ChunkError::InvalidChunkType)),
+        };
+
+        // grow the buffer as bytes arrive rather than trusting the length field
+        let mut data = Vec::new();
+        (&mut self.inner).take(data_length as u64).read_to_end(&mut data)?;
+
+        let mut crc_bytes = [0; Chunk::CRC_BYTES];
+        let crc_read = read_up_to(&mut self.inner, &mut crc_bytes)?;
+
+        if data.len() < data_length || crc_read < crc_bytes.len() {
+            let remaining = data.len() + crc_read;
+            let remaining = remaining.saturating_sub(Chunk::CRC_BYTES);
+            return Err(self.invalid_chunk(ChunkError::Truncated { declared: data_length, remaining }));
+        }
+
+        let chunk = Chunk::from_parts(chunk_type, data, u32::from_be_bytes(crc_bytes))
+            .map_err(|source| self.invalid_chunk(source))?;
+
+        self.offset += chunk.length() + Chunk::METADATA_BYTES;
+        self.index += 1;
+        Ok(Some(chunk))
+    }
+
+    fn invalid_chunk(&self, source: ChunkError) -> crate::Error {
+        PngError::InvalidChunk {
+            index: self.index,
+            offset: self.offset,
+            source,
+        }
+        .into()
+    }
+}
+
+impl<R: Read> Iterator for PngReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let chunk = self.read_chunk().transpose();
+        if !matches!(chunk, Some(Ok(_))) {
+            self.done = true;
+        }
+        chunk
+    }
+}
+
+/// Fills as much of `buf` as the reader can provide, stopping early only at
+/// end of input. Returns how many bytes were read.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            Chunk::new(ChunkType::from_str("FrSt").unwrap(), b"I am the first chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("miDl").unwrap(), b"I am another chunk".to_vec()),
+            Chunk::new(ChunkType::from_str("LASt").unwrap(), b"I am the last chunk".to_vec()),
+        ]
+    }
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(testing_chunks().iter().flat_map(|chunk| chunk.as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_reads_every_chunk() {
+        let reader = PngReader::new(Cursor::new(testing_bytes())).unwrap();
+        let chunks: Vec<Chunk> = reader.collect::<Result<_>>().unwrap();
+
+        assert_eq!(chunks, testing_chunks());
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let mut bytes = testing_bytes();
+        bytes[0] = 13;
+
+        let reader = PngReader::new(Cursor::new(bytes));
+
+        assert!(matches!(reader, Err(Error::Png(PngError::InvalidHeader))));
+    }
+
+    #[test]
+    fn test_short_signature() {
+        let reader = PngReader::new(Cursor::new(&Png::STANDARD_HEADER[..4]));
+
+        assert!(matches!(reader, Err(Error::Png(PngError::TooSmall))));
+    }
+
+    #[test]
+    fn test_truncated_stream() {
+        let bytes = testing_bytes();
+        let reader = PngReader::new(Cursor::new(&bytes[..bytes.len() - 6])).unwrap();
+        let results: Vec<Result<Chunk>> = reader.collect();
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok() && results[1].is_ok());
+        assert!(matches!(
+            results[2],
+            Err(Error::Png(PngError::InvalidChunk { index: 2, source: ChunkError::Truncated { declared: 19, remaining: 13 }, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_matches_slice_parser() {
+        let bytes = testing_bytes();
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+        let chunks: Vec<Chunk> = PngReader::new(bytes.as_slice()).unwrap().collect::<Result<_>>().unwrap();
+
+        assert_eq!(png.chunks(), chunks.as_slice());
+    }
+}
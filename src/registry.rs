@@ -0,0 +1,508 @@
+use std::fmt::Display;
+use crate::apng::{AnimationControl, ApngError, FrameControl, FrameData};
+use crate::chunk_type::{ChunkType, MatchesChunkType};
+use crate::ihdr::{Ihdr, IhdrError};
+use crate::text::{CompressedText, InternationalText, Text, TextError, TextualChunk};
+
+/// What the PNG spec says about a registered chunk type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    pub chunk_type: &'static str,
+    pub name: &'static str,
+    pub placement: Placement,
+    /// Whether a file may contain more than one chunk of this type.
+    pub multiple: bool,
+    pub sample_edit: SampleEdit,
+}
+
+/// Where a chunk type may appear relative to the critical chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    First,
+    Last,
+    /// Before PLTE and the image data.
+    BeforePlte,
+    /// After PLTE, if there is one, and before the image data.
+    AfterPlte,
+    BeforeIdat,
+    /// In a single run of consecutive chunks.
+    Consecutive,
+    AfterIdat,
+    Anywhere,
+}
+
+/// What becomes of a chunk when only the image's sample values change, as
+/// in [`Png::set_pixels`](crate::png::Png::set_pixels), while the header and
+/// palette stay the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleEdit {
+    /// The chunk doesn't depend on the samples, like gAMA or bKGD.
+    Keep,
+    /// The chunk describes the samples themselves, like hIST or sBIT.
+    Drop,
+    /// tRNS: for palette images it gives the alpha of palette entries, but
+    /// otherwise it names one exact sample value as transparent.
+    KeepForPalette,
+    /// tIME: the chunk records when the image last changed, so it is set to
+    /// the time of the edit.
+    Touch,
+}
+
+/// Every chunk type in the PNG 3rd edition spec and the PNG extensions
+/// registry.
+pub const KNOWN_CHUNKS: &[ChunkInfo] = &[
+    known("IHDR", "Image header", Placement::First, false, SampleEdit::Keep),
+    known("PLTE", "Palette", Placement::BeforeIdat, false, SampleEdit::Keep),
+    known("IDAT", "Image data", Placement::Consecutive, true, SampleEdit::Keep),
+    known("IEND", "Image trailer", Placement::Last, false, SampleEdit::Keep),
+    known("tRNS", "Transparency", Placement::AfterPlte, false, SampleEdit::KeepForPalette),
+    known("cHRM", "Primary chromaticities and white point", Placement::BeforePlte, false, SampleEdit::Keep),
+    known("gAMA", "Image gamma", Placement::BeforePlte, false, SampleEdit::Keep),
+    known("iCCP", "Embedded ICC profile", Placement::BeforePlte, false, SampleEdit::Keep),
+    known("sBIT", "Significant bits", Placement::BeforePlte, false, SampleEdit::Drop),
+    known("sRGB", "Standard RGB colour space", Placement::BeforePlte, false, SampleEdit::Keep),
+    known("cICP", "Coding-independent code points", Placement::BeforePlte, false, SampleEdit::Keep),
+    known("mDCV", "Mastering display colour volume", Placement::BeforePlte, false, SampleEdit::Keep),
+    known("cLLI", "Content light level information", Placement::BeforePlte, false, SampleEdit::Drop),
+    known("tEXt", "Textual data", Placement::Anywhere, true, SampleEdit::Keep),
+    known("zTXt", "Compressed textual data", Placement::Anywhere, true, SampleEdit::Keep),
+    known("iTXt", "International textual data", Placement::Anywhere, true, SampleEdit::Keep),
+    known("bKGD", "Background colour", Placement::AfterPlte, false, SampleEdit::Keep),
+    known("hIST", "Image histogram", Placement::AfterPlte, false, SampleEdit::Drop),
+    known("pHYs", "Physical pixel dimensions", Placement::BeforeIdat, false, SampleEdit::Keep),
+    known("sPLT", "Suggested palette", Placement::BeforeIdat, true, SampleEdit::Drop),
+    known("eXIf", "Exchangeable image file profile", Placement::BeforeIdat, false, SampleEdit::Keep),
+    known("tIME", "Image last-modification time", Placement::Anywhere, false, SampleEdit::Touch),
+    known("acTL", "Animation control", Placement::BeforeIdat, false, SampleEdit::Keep),
+    known("fcTL", "Frame control", Placement::Anywhere, true, SampleEdit::Keep),
+    known("fdAT", "Frame data", Placement::AfterIdat, true, SampleEdit::Keep),
+    known("oFFs", "Image offset", Placement::BeforeIdat, false, SampleEdit::Keep),
+    known("pCAL", "Calibration of pixel values", Placement::BeforeIdat, false, SampleEdit::Keep),
+    known("sCAL", "Physical scale of image subject", Placement::BeforeIdat, false, SampleEdit::Keep),
+    known("sTER", "Indicator of stereo image", Placement::BeforeIdat, false, SampleEdit::Keep),
+    known("gIFg", "GIF graphic control extension", Placement::Anywhere, true, SampleEdit::Keep),
+    known("gIFx", "GIF application extension", Placement::Anywhere, true, SampleEdit::Keep),
+    known("gIFt", "GIF plain text extension", Placement::Anywhere, true, SampleEdit::Keep),
+    known("fRAc", "Fractal image parameters", Placement::Anywhere, true, SampleEdit::Keep),
+    known("dSIG", "Digital signature", Placement::Anywhere, true, SampleEdit::Drop),
+];
+
+const fn known(
+    chunk_type: &'static str,
+    name: &'static str,
+    placement: Placement,
+    multiple: bool,
+    sample_edit: SampleEdit,
+) -> ChunkInfo {
+    ChunkInfo { chunk_type, name, placement, multiple, sample_edit }
+}
+
+/// Looks up a registered chunk type.
+pub fn lookup(chunk_type: &ChunkType) -> Option<&'static ChunkInfo> {
+    KNOWN_CHUNKS.iter().find(|info| info.chunk_type.matches_chunk_type(chunk_type))
+}
+
+/// The fields of a registered chunk, for the types whose data can be read
+/// without looking at other chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkData {
+    Header(Ihdr),
+    Palette { entries: usize },
+    End,
+    /// Chromaticities in units of 1/100000: white point, red, green, blue.
+    Chromaticities([(u32, u32); 4]),
+    /// Gamma in units of 1/100000.
+    Gamma(u32),
+    IccProfile { name: String, compressed_length: usize },
+    StandardRgb { rendering_intent: u8 },
+    CodingPoints { colour_primaries: u8, transfer_function: u8, matrix_coefficients: u8, full_range: bool },
+    /// Primaries and white point in units of 1/50000, luminances in units
+    /// of 1/10000 cd/m².
+    MasteringDisplay { primaries: [(u16, u16); 3], white_point: (u16, u16), max_luminance: u32, min_luminance: u32 },
+    /// Light levels in units of 1/10000 cd/m².
+    ContentLightLevel { max_content: u32, max_frame_average: u32 },
+    Text(TextualChunk),
+    Histogram { entries: usize },
+    PhysicalDimensions { x: u32, y: u32, in_metres: bool },
+    Exif { big_endian: bool, length: usize },
+    Time { year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8 },
+    Offset { x: i32, y: i32, in_micrometres: bool },
+    Stereo { diverging: bool },
+    AnimationControl(AnimationControl),
+    FrameControl(FrameControl),
+    FrameData { sequence_number: u32, length: usize },
+}
+
+/// Decodes the data of a registered chunk type. Returns `Ok(None)` for
+/// unknown chunk types and for types like tRNS whose layout depends on the
+/// image header, and an error for malformed chunks.
+pub fn decode(chunk_type: &ChunkType, data: &[u8]) -> Result<Option<ChunkData>, RegistryError> {
+    let decoded = match &chunk_type.bytes() {
+        b"IHDR" => ChunkData::Header(Ihdr::try_from(data)?),
+        b"PLTE" if data.len().is_multiple_of(3) => ChunkData::Palette { entries: data.len() / 3 },
+        b"IEND" if data.is_empty() => ChunkData::End,
+        b"cHRM" if data.len() == 32 => {
+            let mut points = [(0, 0); 4];
+            for (i, point) in points.iter_mut().enumerate() {
+                *point = (be_u32(data, i * 8)?, be_u32(data, i * 8 + 4)?);
+            }
+            ChunkData::Chromaticities(points)
+        }
+        b"gAMA" if data.len() == 4 => ChunkData::Gamma(be_u32(data, 0)?),
+        b"iCCP" => {
+            let end = data.iter().position(|&b| b == 0).ok_or(RegistryError::Malformed)?;
+            ChunkData::IccProfile {
+                name: data[..end].iter().map(|&b| b as char).collect(),
+                compressed_length: data.len().checked_sub(end + 2).ok_or(RegistryError::Malformed)?,
+            }
+        }
+        b"sRGB" if data.len() == 1 => ChunkData::StandardRgb { rendering_intent: data[0] },
+        b"cICP" if data.len() == 4 => ChunkData::CodingPoints {
+            colour_primaries: data[0],
+            transfer_function: data[1],
+            matrix_coefficients: data[2],
+            full_range: data[3] == 1,
+        },
+        b"mDCV" if data.len() == 24 => ChunkData::MasteringDisplay {
+            primaries: [
+                (be_u16(data, 0)?, be_u16(data, 2)?),
+                (be_u16(data, 4)?, be_u16(data, 6)?),
+                (be_u16(data, 8)?, be_u16(data, 10)?),
+            ],
+            white_point: (be_u16(data, 12)?, be_u16(data, 14)?),
+            max_luminance: be_u32(data, 16)?,
+            min_luminance: be_u32(data, 20)?,
+        },
+        b"cLLI" if data.len() == 8 => ChunkData::ContentLightLevel {
+            max_content: be_u32(data, 0)?,
+            max_frame_average: be_u32(data, 4)?,
+        },
+        b"tEXt" => ChunkData::Text(TextualChunk::Text(Text::try_from(data)?)),
+        b"zTXt" => ChunkData::Text(TextualChunk::Compressed(CompressedText::try_from(data)?)),
+        b"iTXt" => ChunkData::Text(TextualChunk::International(InternationalText::try_from(data)?)),
+        b"hIST" if data.len().is_multiple_of(2) => ChunkData::Histogram { entries: data.len() / 2 },
+        b"pHYs" if data.len() == 9 => ChunkData::PhysicalDimensions {
+            x: be_u32(data, 0)?,
+            y: be_u32(data, 4)?,
+            in_metres: data[8] == 1,
+        },
+        b"eXIf" => ChunkData::Exif {
+            big_endian: match data.get(..4) {
+                Some(b"MM\0\x2a") => true,
+                Some(b"II\x2a\0") => false,
+                _ => return Err(RegistryError::Malformed),
+            },
+            length: data.len(),
+        },
+        b"tIME" if data.len() == 7 => ChunkData::Time {
+            year: be_u16(data, 0)?,
+            month: data[2],
+            day: data[3],
+            hour: data[4],
+            minute: data[5],
+            second: data[6],
+        },
+        b"oFFs" if data.len() == 9 => ChunkData::Offset {
+            x: be_u32(data, 0)? as i32,
+            y: be_u32(data, 4)? as i32,
+            in_micrometres: data[8] == 1,
+        },
+        b"sTER" if data.len() == 1 && data[0] <= 1 => ChunkData::Stereo { diverging: data[0] == 1 },
+        b"acTL" => ChunkData::AnimationControl(AnimationControl::try_from(data)?),
+        b"fcTL" => ChunkData::FrameControl(FrameControl::try_from(data)?),
+        b"fdAT" => {
+            let frame_data = FrameData::try_from(data)?;
+            ChunkData::FrameData { sequence_number: frame_data.sequence_number, length: frame_data.data.len() }
+        }
+        // every other registered type with a fixed layout fell through its
+        // length guard above
+        b"PLTE" | b"IEND" | b"cHRM" | b"gAMA" | b"sRGB" | b"cICP" | b"mDCV" | b"cLLI" | b"hIST" | b"pHYs"
+        | b"tIME" | b"oFFs" | b"sTER" => return Err(RegistryError::Malformed),
+        _ => return Ok(None),
+    };
+    Ok(Some(decoded))
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Result<u16, RegistryError> {
+    let bytes = data.get(offset..offset + 2).ok_or(RegistryError::Malformed)?;
+    Ok(u16::from_be_bytes(bytes.try_into().expect("two bytes")))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Result<u32, RegistryError> {
+    let bytes = data.get(offset..offset + 4).ok_or(RegistryError::Malformed)?;
+    Ok(u32::from_be_bytes(bytes.try_into().expect("four bytes")))
+}
+
+/// Formats a fixed point value stored in units of 1/`scale`.
+fn fixed(value: u32, scale: u32) -> String {
+    let decimals = (scale as f64).log10().ceil() as usize;
+    format!("{:.*}", decimals, value as f64 / scale as f64)
+}
+
+impl Display for Placement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Placement::First => write!(f, "first"),
+            Placement::Last => write!(f, "last"),
+            Placement::BeforePlte => write!(f, "before PLTE and IDAT"),
+            Placement::AfterPlte => write!(f, "after PLTE and before IDAT"),
+            Placement::BeforeIdat => write!(f, "before IDAT"),
+            Placement::Consecutive => write!(f, "consecutive"),
+            Placement::AfterIdat => write!(f, "after IDAT"),
+            Placement::Anywhere => write!(f, "anywhere"),
+        }
+    }
+}
+
+/// Writes one indented `Field: value` line per field, to sit inside a
+/// chunk's `Display` output.
+impl Display for ChunkData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkData::Header(ihdr) => {
+                writeln!(f, "  Dimensions: {}x{}", ihdr.width, ihdr.height)?;
+                writeln!(f, "  Bit depth: {}", ihdr.bit_depth)?;
+                writeln!(f, "  Color type: {}", ihdr.color_type)?;
+                writeln!(f, "  Compression method: {}", ihdr.compression_method)?;
+                writeln!(f, "  Filter method: {}", ihdr.filter_method)?;
+                writeln!(f, "  Interlace: {}", ihdr.interlace_method)
+            }
+            ChunkData::Palette { entries } => writeln!(f, "  Entries: {}", entries),
+            ChunkData::End => Ok(()),
+            ChunkData::Chromaticities(points) => {
+                for (label, (x, y)) in ["White point", "Red", "Green", "Blue"].iter().zip(points) {
+                    writeln!(f, "  {}: ({}, {})", label, fixed(*x, 100_000), fixed(*y, 100_000))?;
+                }
+                Ok(())
+            }
+            ChunkData::Gamma(gamma) => writeln!(f, "  Gamma: {}", fixed(*gamma, 100_000)),
+            ChunkData::IccProfile { name, compressed_length } => {
+                writeln!(f, "  Profile: {}", name)?;
+                writeln!(f, "  Compressed size: {} bytes", compressed_length)
+            }
+            ChunkData::StandardRgb { rendering_intent } => {
+                let intent = match rendering_intent {
+                    0 => "perceptual",
+                    1 => "relative colorimetric",
+                    2 => "saturation",
+                    3 => "absolute colorimetric",
+                    _ => "unknown",
+                };
+                writeln!(f, "  Rendering intent: {} ({})", intent, rendering_intent)
+            }
+            ChunkData::CodingPoints { colour_primaries, transfer_function, matrix_coefficients, full_range } => {
+                writeln!(f, "  Colour primaries: {}", colour_primaries)?;
+                writeln!(f, "  Transfer function: {}", transfer_function)?;
+                writeln!(f, "  Matrix coefficients: {}", matrix_coefficients)?;
+                writeln!(f, "  Full range: {}", if *full_range { "yes" } else { "no" })
+            }
+            ChunkData::MasteringDisplay { primaries, white_point, max_luminance, min_luminance } => {
+                for (label, (x, y)) in ["Red", "Green", "Blue"].iter().zip(primaries) {
+                    writeln!(f, "  {}: ({}, {})", label, fixed(*x as u32, 50_000), fixed(*y as u32, 50_000))?;
+                }
+                writeln!(f, "  White point: ({}, {})", fixed(white_point.0 as u32, 50_000), fixed(white_point.1 as u32, 50_000))?;
+                writeln!(f, "  Max luminance: {} cd/m²", fixed(*max_luminance, 10_000))?;
+                writeln!(f, "  Min luminance: {} cd/m²", fixed(*min_luminance, 10_000))
+            }
+            ChunkData::ContentLightLevel { max_content, max_frame_average } => {
+                writeln!(f, "  Max content light level: {} cd/m²", fixed(*max_content, 10_000))?;
+                writeln!(f, "  Max frame-average light level: {} cd/m²", fixed(*max_frame_average, 10_000))
+            }
+            ChunkData::Text(text) => {
+                writeln!(f, "  Keyword: {}", text.keyword())?;
+                if let TextualChunk::International(text) = text {
+                    if !text.language_tag().is_empty() {
+                        writeln!(f, "  Language: {}", text.language_tag())?;
+                    }
+                    if !text.translated_keyword().is_empty() {
+                        writeln!(f, "  Translated keyword: {}", text.translated_keyword())?;
+                    }
+                }
+                writeln!(f, "  Text: {}", text.text())
+            }
+            ChunkData::Histogram { entries } => writeln!(f, "  Entries: {}", entries),
+            ChunkData::PhysicalDimensions { x, y, in_metres } => match in_metres {
+                true => writeln!(f, "  Pixels per metre: {}x{}", x, y),
+                false => writeln!(f, "  Aspect ratio: {}:{}", x, y),
+            },
+            ChunkData::Exif { big_endian, length } => {
+                writeln!(f, "  Byte order: {}", if *big_endian { "big endian" } else { "little endian" })?;
+                writeln!(f, "  Size: {} bytes", length)
+            }
+            ChunkData::Time { year, month, day, hour, minute, second } => writeln!(
+                f,
+                "  Modified: {:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+                year, month, day, hour, minute, second
+            ),
+            ChunkData::Offset { x, y, in_micrometres } => {
+                writeln!(f, "  Offset: {}, {} {}", x, y, if *in_micrometres { "micrometres" } else { "pixels" })
+            }
+            ChunkData::Stereo { diverging } => {
+                writeln!(f, "  Layout: {}", if *diverging { "diverging fuse" } else { "cross fuse" })
+            }
+            ChunkData::AnimationControl(control) => {
+                writeln!(f, "  Frames: {}", control.num_frames)?;
+                match control.num_plays {
+                    0 => writeln!(f, "  Plays: forever"),
+                    plays => writeln!(f, "  Plays: {}", plays),
+                }
+            }
+            ChunkData::FrameControl(frame) => {
+                writeln!(f, "  Sequence number: {}", frame.sequence_number)?;
+                writeln!(f, "  Region: {}x{} at ({}, {})", frame.width, frame.height, frame.x_offset, frame.y_offset)?;
+                let delay_den = if frame.delay_den == 0 { 100 } else { frame.delay_den };
+                writeln!(f, "  Delay: {}/{} s", frame.delay_num, delay_den)?;
+                writeln!(f, "  Dispose: {:?}, blend: {:?}", frame.dispose_op, frame.blend_op)
+            }
+            ChunkData::FrameData { sequence_number, length } => {
+                writeln!(f, "  Sequence number: {}", sequence_number)?;
+                writeln!(f, "  Data: {} bytes", length)
+            }
+        }
+    }
+}
+
+/// Why the data of a registered chunk type couldn't be decoded.
+#[derive(Debug)]
+pub enum RegistryError {
+    /// The data has the wrong length or doesn't follow the type's layout.
+    Malformed,
+    Ihdr(IhdrError),
+    Text(TextError),
+    Apng(ApngError),
+}
+
+impl std::error::Error for RegistryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RegistryError::Malformed => None,
+            RegistryError::Ihdr(err) => Some(err),
+            RegistryError::Text(err) => Some(err),
+            RegistryError::Apng(err) => Some(err),
+        }
+    }
+}
+
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::Malformed => write!(f, "The chunk data doesn't match the layout of its type"),
+            RegistryError::Ihdr(err) => write!(f, "{}", err),
+            RegistryError::Text(err) => write!(f, "{}", err),
+            RegistryError::Apng(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<IhdrError> for RegistryError {
+    fn from(err: IhdrError) -> Self {
+        RegistryError::Ihdr(err)
+    }
+}
+
+impl From<TextError> for RegistryError {
+    fn from(err: TextError) -> Self {
+        RegistryError::Text(err)
+    }
+}
+
+impl From<ApngError> for RegistryError {
+    fn from(err: ApngError) -> Self {
+        RegistryError::Apng(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::str::FromStr;
+    use crate::chunk::Chunk;
+
+    #[test]
+    fn test_registry_entries() {
+        let mut seen = HashSet::new();
+        for info in KNOWN_CHUNKS {
+            let chunk_type = ChunkType::from_str(info.chunk_type).unwrap();
+            assert!(chunk_type.is_valid(), "{}", info.chunk_type);
+            // the APNG chunks were named before they were registered
+            let is_apng = ["acTL", "fcTL", "fdAT"].contains(&info.chunk_type);
+            assert_eq!(chunk_type.is_public(), !is_apng, "{}", info.chunk_type);
+            assert!(seen.insert(info.chunk_type), "{} is listed twice", info.chunk_type);
+        }
+
+        assert_eq!(lookup(&ChunkType::IHDR).unwrap().placement, Placement::First);
+        assert_eq!(lookup(&ChunkType::from_str("gAMA").unwrap()).unwrap().name, "Image gamma");
+        assert!(lookup(&ChunkType::from_str("ruSt").unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_decode_physical_dimensions() {
+        let data = [0, 0, 0x0e, 0xc3, 0, 0, 0x0e, 0xc3, 1];
+        let decoded = decode(&ChunkType::from_str("pHYs").unwrap(), &data).unwrap().unwrap();
+
+        assert_eq!(decoded, ChunkData::PhysicalDimensions { x: 3779, y: 3779, in_metres: true });
+        assert_eq!(decoded.to_string(), "  Pixels per metre: 3779x3779\n");
+    }
+
+    #[test]
+    fn test_decode_gamma() {
+        let decoded = decode(&ChunkType::from_str("gAMA").unwrap(), &45455u32.to_be_bytes()).unwrap().unwrap();
+        assert_eq!(decoded.to_string(), "  Gamma: 0.45455\n");
+    }
+
+    #[test]
+    fn test_decode_time() {
+        let data = [0x07, 0xea, 10, 16, 9, 5, 0];
+        let decoded = decode(&ChunkType::from_str("tIME").unwrap(), &data).unwrap().unwrap();
+        assert_eq!(decoded.to_string(), "  Modified: 2026-10-16 09:05:00 UTC\n");
+    }
+
+    #[test]
+    fn test_decode_text() {
+        let decoded = decode(&ChunkType::TEXT, b"Comment\0hello").unwrap().unwrap();
+        assert_eq!(decoded.to_string(), "  Keyword: Comment\n  Text: hello\n");
+    }
+
+    #[test]
+    fn test_decode_header() {
+        let data = [0, 0, 0, 4, 0, 0, 0, 2, 8, 6, 0, 0, 1];
+        let decoded = decode(&ChunkType::IHDR, &data).unwrap().unwrap();
+        assert_eq!(
+            decoded.to_string(),
+            "  Dimensions: 4x2\n  Bit depth: 8\n  Color type: RGBA\n  Compression method: 0\n  Filter method: 0\n  Interlace: Adam7\n"
+        );
+    }
+
+    #[test]
+    fn test_decode_malformed() {
+        assert!(matches!(decode(&ChunkType::from_str("gAMA").unwrap(), &[0, 1]), Err(RegistryError::Malformed)));
+        assert!(matches!(decode(&ChunkType::from_str("eXIf").unwrap(), b"nope"), Err(RegistryError::Malformed)));
+        let data = [0, 0, 0, 4, 0, 0, 0, 2, 3, 6, 0, 0, 0];
+        assert!(matches!(decode(&ChunkType::IHDR, &data), Err(RegistryError::Ihdr(IhdrError::InvalidBitDepth(3, _)))));
+    }
+
+    #[test]
+    fn test_decode_unknown() {
+        assert_eq!(decode(&ChunkType::from_str("tRNS").unwrap(), &[0, 0]).unwrap(), None);
+        assert_eq!(decode(&ChunkType::from_str("ruSt").unwrap(), b"message").unwrap(), None);
+    }
+
+    #[test]
+    fn test_chunk_display_shows_fields() {
+        let chunk = Chunk::new(ChunkType::from_str("sRGB").unwrap(), vec![0]);
+        let display = chunk.to_string();
+
+        assert!(display.contains("  Type: sRGB (Standard RGB colour space)\n"));
+        assert!(display.contains("  Rendering intent: perceptual (0)\n"));
+        assert!(!display.contains("Data:"));
+
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"message".to_vec());
+        assert!(chunk.to_string().contains("  Data: 7 bytes\n"));
+
+        let chunk = Chunk::new(ChunkType::IHDR, vec![0; 13]);
+        let display = chunk.to_string();
+        assert!(display.contains("  Data: 13 bytes\n"));
+        assert!(display.contains("  Error: "));
+    }
+}
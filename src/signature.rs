@@ -0,0 +1,440 @@
+use std::fmt::Display;
+use sha2::{Digest, Sha256};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::keys::{SigningKey, VerifyingKey};
+use crate::png::{Png, PngError};
+use crate::Result;
+
+/// Version of the signature chunk layout.
+pub const VERSION: u8 = 1;
+/// Flag set when the signature also covers [`image_digest`].
+pub const FLAG_COVERS_IMAGE: u8 = 1;
+
+/// Size of a signature chunk's data: version, flags, the signed chunk type,
+/// the verifying key and the signature itself.
+pub const SIGNATURE_DATA_LENGTH: usize = 2 + 4 + 32 + 64;
+
+const DOMAIN: &[u8] = b"png-msg signature v1\0";
+
+/// The parsed contents of a signature chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub covers_image: bool,
+    pub message_type: ChunkType,
+    pub signer: VerifyingKey,
+    signature: [u8; 64],
+}
+
+/// The outcome of checking one signature chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    /// `None` when the chunk is too malformed to name its signer.
+    pub signer: Option<VerifyingKey>,
+    pub covers_image: bool,
+    pub status: SignatureStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Valid,
+    /// The signature doesn't match the message or, if it covers it, the
+    /// image data.
+    Invalid,
+    /// The file has no chunk of the signed type.
+    MessageMissing,
+    /// The signature chunk itself doesn't parse.
+    Malformed(SignatureError),
+}
+
+impl Signature {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(SIGNATURE_DATA_LENGTH);
+        data.push(VERSION);
+        data.push(if self.covers_image { FLAG_COVERS_IMAGE } else { 0 });
+        data.extend_from_slice(&self.message_type.bytes());
+        data.extend_from_slice(self.signer.as_bytes());
+        data.extend_from_slice(&self.signature);
+        Chunk::new(ChunkType::SIGNATURE, data)
+    }
+}
+
+impl TryFrom<&[u8]> for Signature {
+    type Error = SignatureError;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        let bytes: &[u8; SIGNATURE_DATA_LENGTH] = value.try_into().map_err(|_| SignatureError::InvalidLength(value.len()))?;
+
+        if bytes[0] != VERSION {
+            return Err(SignatureError::UnsupportedVersion(bytes[0]));
+        }
+        if bytes[1] & !FLAG_COVERS_IMAGE != 0 {
+            return Err(SignatureError::UnknownFlags(bytes[1]));
+        }
+
+        let message_type = ChunkType::try_from([bytes[2], bytes[3], bytes[4], bytes[5]])
+            .map_err(|_| SignatureError::InvalidMessageType)?;
+        let mut signer = [0; 32];
+        signer.copy_from_slice(&bytes[6..38]);
+        let signer = VerifyingKey::from_bytes(signer).map_err(|_| SignatureError::InvalidSigner)?;
+        let mut signature = [0; 64];
+        signature.copy_from_slice(&bytes[38..]);
+
+        Ok(Signature { covers_image: bytes[1] & FLAG_COVERS_IMAGE != 0, message_type, signer, signature })
+    }
+}
+
+/// SHA-256 over the image-defining chunks in a form that doesn't depend on
+/// how the image data is split into IDAT chunks: the IHDR data, the PLTE
+/// data if there is one, then all IDAT data, each behind its type and
+/// length.
+pub fn image_digest(chunks: &[Chunk]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+
+    for chunk_type in [ChunkType::IHDR, ChunkType::PLTE] {
+        if let Some(chunk) = chunks.iter().find(|c| *c.chunk_type() == chunk_type) {
+            hasher.update(chunk_type.bytes());
+            hasher.update((chunk.data().len() as u64).to_be_bytes());
+            hasher.update(chunk.data());
+        }
+    }
+
+    let idats = || chunks.iter().filter(|c| *c.chunk_type() == ChunkType::IDAT);
+    let idat_length: u64 = idats().map(|c| c.data().len() as u64).sum();
+    hasher.update(ChunkType::IDAT.bytes());
+    hasher.update(idat_length.to_be_bytes());
+    for chunk in idats() {
+        hasher.update(chunk.data());
+    }
+
+    hasher.finalize().into()
+}
+
+/// Signs `message`, every chunk of one type in file order, and returns the
+/// signature chunk. With `image` set, the signature also covers the
+/// [`image_digest`] of those chunks.
+pub fn sign_chunks(message: &[&Chunk], image: Option<&[Chunk]>, key: &SigningKey) -> Result<Chunk> {
+    let message_type = message.first().ok_or(PngError::UnknownChunkType)?.chunk_type();
+    let digest = image.map(image_digest);
+    let signature = key.sign(&signed_bytes(message, digest.as_ref()));
+
+    Ok(Signature { covers_image: digest.is_some(), message_type: message_type.clone(), signer: key.verifying_key(), signature }
+        .to_chunk())
+}
+
+/// Checks a signature against `message`, the chunks of the type it names,
+/// with `chunks` as the image data if the signature covers it.
+pub fn verify_chunks(signature: &Signature, message: &[&Chunk], chunks: &[Chunk]) -> SignatureStatus {
+    if message.is_empty() || message.iter().any(|chunk| *chunk.chunk_type() != signature.message_type) {
+        return SignatureStatus::MessageMissing;
+    }
+
+    let digest = signature.covers_image.then(|| image_digest(chunks));
+    if signature.signer.verify(&signed_bytes(message, digest.as_ref()), &signature.signature) {
+        SignatureStatus::Valid
+    } else {
+        SignatureStatus::Invalid
+    }
+}
+
+/// Signs every chunk of `message_type`, so a message split across several
+/// chunks is covered as a whole, and inserts the signature chunk right after
+/// the last of them.
+pub fn sign(png: &mut Png, message_type: &ChunkType, key: &SigningKey, covers_image: bool) -> Result<()> {
+    let message: Vec<&Chunk> = png.chunks_by_type(message_type).collect();
+    let signature = sign_chunks(&message, covers_image.then_some(png.chunks()), key)?;
+
+    let last = png.chunks().iter().rposition(|chunk| chunk.chunk_type() == message_type);
+    png.insert_chunk_at(last.map_or(0, |last| last + 1), signature)
+}
+
+/// Checks every signature chunk for `message_type`. A signature covers all
+/// chunks of that type in the file, wherever they are.
+pub fn verify(png: &Png, message_type: &ChunkType) -> Result<Vec<Verification>> {
+    let message: Vec<&Chunk> = png.chunks_by_type(message_type).collect();
+    let mut verifications = Vec::new();
+
+    for chunk in png.chunks_by_type(ChunkType::SIGNATURE) {
+        // anyone can append a signature chunk, so a broken one is reported
+        // without hiding the others
+        let signature = match Signature::try_from(chunk.data()) {
+            Ok(signature) => signature,
+            Err(_) if names_other_type(chunk.data(), message_type) => continue,
+            Err(err) => {
+                let status = SignatureStatus::Malformed(err);
+                verifications.push(Verification { signer: None, covers_image: false, status });
+                continue;
+            }
+        };
+        if signature.message_type != *message_type {
+            continue;
+        }
+
+        let status = verify_chunks(&signature, &message, png.chunks());
+        verifications.push(Verification { signer: Some(signature.signer), covers_image: signature.covers_image, status });
+    }
+
+    if verifications.is_empty() {
+        return Err(SignatureError::NotSigned.into());
+    }
+    Ok(verifications)
+}
+
+/// Whether the data of a signature chunk that doesn't parse still names a
+/// valid message type other than `message_type`.
+fn names_other_type(data: &[u8], message_type: &ChunkType) -> bool {
+    let Some(&[a, b, c, d]) = data.get(2..6) else {
+        return false;
+    };
+    ChunkType::try_from([a, b, c, d]).is_ok_and(|chunk_type| chunk_type != *message_type)
+}
+
+/// Each chunk goes in behind its type and length, so signatures made when
+/// only one chunk was signed still verify.
+fn signed_bytes(message: &[&Chunk], image_digest: Option<&[u8; 32]>) -> Vec<u8> {
+    let length: usize = message.iter().map(|chunk| 8 + chunk.data().len()).sum();
+    let mut bytes = Vec::with_capacity(DOMAIN.len() + 1 + length + 32);
+    bytes.extend_from_slice(DOMAIN);
+    bytes.push(if image_digest.is_some() { FLAG_COVERS_IMAGE } else { 0 });
+    for chunk in message {
+        bytes.extend_from_slice(&chunk.chunk_type().bytes());
+        bytes.extend_from_slice(&(chunk.length() as u32).to_be_bytes());
+        bytes.extend_from_slice(chunk.data());
+    }
+    if let Some(digest) = image_digest {
+        bytes.extend_from_slice(digest);
+    }
+    bytes
+}
+
+impl Display for SignatureStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureStatus::Valid => write!(f, "valid"),
+            SignatureStatus::Invalid => write!(f, "INVALID"),
+            SignatureStatus::MessageMissing => write!(f, "signed message is missing"),
+            SignatureStatus::Malformed(err) => write!(f, "MALFORMED: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    InvalidLength(usize),
+    UnsupportedVersion(u8),
+    UnknownFlags(u8),
+    InvalidMessageType,
+    InvalidSigner,
+    NotSigned,
+    VerificationFailed,
+}
+
+impl std::error::Error for SignatureError {}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::InvalidLength(length) => write!(
+                f,
+                "Signature chunk data must be {} bytes but is {}",
+                SIGNATURE_DATA_LENGTH, length
+            ),
+            SignatureError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported signature version {}", version)
+            }
+            SignatureError::UnknownFlags(flags) => write!(f, "Unknown signature flags {:#04x}", flags),
+            SignatureError::InvalidMessageType => write!(f, "The signature names an invalid chunk type"),
+            SignatureError::InvalidSigner => write!(f, "The signature's verifying key is not a valid Ed25519 key"),
+            SignatureError::NotSigned => write!(f, "No signature found for this chunk type"),
+            SignatureError::VerificationFailed => write!(f, "Signature verification failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::{ColorType, Ihdr, InterlaceMethod};
+    use crate::pixels;
+    use crate::split;
+    use crate::Error;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        let ihdr = Ihdr {
+            width: 4,
+            height: 4,
+            bit_depth: 8,
+            color_type: ColorType::Grayscale,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        };
+        let mut png = Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new(ChunkType::IDAT, pixels::encode(&ihdr, &[128; 16]).unwrap()),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ]);
+        let message = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"signed message".to_vec());
+        png.append_chunk(message).unwrap();
+        png
+    }
+
+    fn message_type() -> ChunkType {
+        ChunkType::from_str("ruSt").unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SigningKey::generate().unwrap();
+        let mut png = testing_png();
+
+        sign(&mut png, &message_type(), &key, false).unwrap();
+        assert_eq!(png.chunks()[3].chunk_type(), &ChunkType::SIGNATURE);
+        assert!(png.validate().is_ok());
+
+        let verifications = verify(&png, &message_type()).unwrap();
+        assert_eq!(
+            verifications,
+            [Verification { signer: Some(key.verifying_key()), covers_image: false, status: SignatureStatus::Valid }]
+        );
+    }
+
+    #[test]
+    fn test_edited_message_is_invalid() {
+        let key = SigningKey::generate().unwrap();
+        let mut png = testing_png();
+        sign(&mut png, &message_type(), &key, false).unwrap();
+
+        let forged = Chunk::new(message_type(), b"forged message".to_vec());
+        png.replace_chunk(message_type(), forged).unwrap();
+
+        assert_eq!(verify(&png, &message_type()).unwrap()[0].status, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_every_part_of_a_split_message_is_signed() {
+        let key = SigningKey::generate().unwrap();
+        let mut png = testing_png();
+        split::append_message(&mut png, &message_type(), vec![b'a'; 200], Some(split::HEADER_LENGTH + 50)).unwrap();
+        assert!(png.chunks_by_type(message_type()).count() > 2);
+        sign(&mut png, &message_type(), &key, false).unwrap();
+        assert_eq!(verify(&png, &message_type()).unwrap()[0].status, SignatureStatus::Valid);
+
+        // flip a bit in the last part
+        let mut chunks: Vec<Chunk> =
+            png.chunks().iter().map(|c| Chunk::new(c.chunk_type().clone(), c.data().to_vec())).collect();
+        let last = chunks.iter().rposition(|c| *c.chunk_type() == message_type()).unwrap();
+        let mut data = chunks[last].data().to_vec();
+        *data.last_mut().unwrap() ^= 1;
+        chunks[last] = Chunk::new(message_type(), data);
+        let png = Png::from_chunks(chunks);
+
+        assert_eq!(verify(&png, &message_type()).unwrap()[0].status, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_malformed_signature_is_reported_with_the_others() {
+        let key = SigningKey::generate().unwrap();
+        let mut png = testing_png();
+        sign(&mut png, &message_type(), &key, false).unwrap();
+        png.insert_before(ChunkType::IEND, Chunk::new(ChunkType::SIGNATURE, vec![1, 0, 0])).unwrap();
+        // an unsupported signature of another message type is left out
+        let mut other = png.chunk_by_type(ChunkType::SIGNATURE).unwrap().data().to_vec();
+        other[0] = 9;
+        other[2..6].copy_from_slice(b"teXt");
+        png.insert_before(ChunkType::IEND, Chunk::new(ChunkType::SIGNATURE, other)).unwrap();
+
+        let verifications = verify(&png, &message_type()).unwrap();
+        assert_eq!(verifications.len(), 2);
+        assert_eq!(verifications[0].status, SignatureStatus::Valid);
+        assert_eq!(verifications[1].signer, None);
+        assert_eq!(verifications[1].status, SignatureStatus::Malformed(SignatureError::InvalidLength(3)));
+    }
+
+    #[test]
+    fn test_image_digest_covers_pixels() {
+        let key = SigningKey::generate().unwrap();
+        let mut covered = testing_png();
+        sign(&mut covered, &message_type(), &key, true).unwrap();
+        let mut uncovered = testing_png();
+        sign(&mut uncovered, &message_type(), &key, false).unwrap();
+
+        // swap the image data directly, set_pixels would drop the covering signature
+        for png in [&mut covered, &mut uncovered] {
+            let ihdr = png.ihdr().unwrap();
+            let mut pixels = png.pixels().unwrap();
+            pixels[0] ^= 0xFF;
+            let idat = Chunk::new(ChunkType::IDAT, pixels::encode(&ihdr, &pixels).unwrap());
+            png.replace_chunk(ChunkType::IDAT, idat).unwrap();
+        }
+
+        assert_eq!(verify(&covered, &message_type()).unwrap()[0].status, SignatureStatus::Invalid);
+        assert_eq!(verify(&uncovered, &message_type()).unwrap()[0].status, SignatureStatus::Valid);
+    }
+
+    #[test]
+    fn test_set_pixels_drops_covering_signature() {
+        let key = SigningKey::generate().unwrap();
+        let mut covered = testing_png();
+        sign(&mut covered, &message_type(), &key, true).unwrap();
+        let mut uncovered = testing_png();
+        sign(&mut uncovered, &message_type(), &key, false).unwrap();
+
+        let pixels = covered.pixels().unwrap();
+        let dropped = covered.set_pixels(&pixels).unwrap();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(*dropped[0].chunk_type(), ChunkType::SIGNATURE);
+
+        assert!(uncovered.set_pixels(&pixels).unwrap().is_empty());
+        assert_eq!(verify(&uncovered, &message_type()).unwrap()[0].status, SignatureStatus::Valid);
+    }
+
+    #[test]
+    fn test_image_digest_ignores_idat_split() {
+        let png = testing_png();
+        let idat = png.chunk_by_type("IDAT").unwrap().data();
+        let (first, second) = idat.split_at(4);
+
+        let mut split: Vec<Chunk> = png
+            .chunks()
+            .iter()
+            .map(|c| Chunk::new(c.chunk_type().clone(), c.data().to_vec()))
+            .collect();
+        split.splice(1..2, [Chunk::new(ChunkType::IDAT, first.to_vec()), Chunk::new(ChunkType::IDAT, second.to_vec())]);
+
+        assert_eq!(image_digest(&split), image_digest(png.chunks()));
+    }
+
+    #[test]
+    fn test_not_signed() {
+        let result = verify(&testing_png(), &message_type());
+        assert!(matches!(result, Err(Error::Signature(SignatureError::NotSigned))));
+    }
+
+    #[test]
+    fn test_signature_chunk_round_trip() {
+        let key = SigningKey::generate().unwrap();
+        let png = testing_png();
+        let message: Vec<&Chunk> = png.chunks_by_type("ruSt").collect();
+        let chunk = sign_chunks(&message, Some(png.chunks()), &key).unwrap();
+
+        let signature = Signature::try_from(chunk.data()).unwrap();
+        assert!(signature.covers_image);
+        assert_eq!(signature.message_type, message_type());
+        assert_eq!(signature.signer, key.verifying_key());
+        assert_eq!(signature.to_chunk(), chunk);
+    }
+
+    #[test]
+    fn test_malformed_signature_chunk() {
+        assert_eq!(Signature::try_from(&[1, 0, 0][..]), Err(SignatureError::InvalidLength(3)));
+
+        let key = SigningKey::generate().unwrap();
+        let png = testing_png();
+        let message: Vec<&Chunk> = png.chunks_by_type("ruSt").collect();
+        let mut data = sign_chunks(&message, None, &key).unwrap().data().to_vec();
+        data[0] = 2;
+        assert_eq!(Signature::try_from(data.as_slice()), Err(SignatureError::UnsupportedVersion(2)));
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use sha2::{Digest, Sha256};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope::{self, Flags};
+use crate::png::Png;
+
+/// Envelope flags that mark chunk data holding one part of a split message.
+const PART_FLAGS: Flags = Flags { encrypted: false, compressed: false, part: true };
+/// Envelope header, message ID, part index, part count and the SHA-256
+/// digest of the whole message.
+pub const HEADER_LENGTH: usize = envelope::HEADER_LENGTH + 8 + 4 + 4 + 32;
+
+/// One piece of a message that was split across several chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+    /// Random ID shared by every part of the same message.
+    pub message_id: u64,
+    pub index: u32,
+    pub total: u32,
+    /// SHA-256 of the whole message, repeated in every part.
+    pub digest: [u8; 32],
+    pub data: Vec<u8>,
+}
+
+/// A message read back from its chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// The ID of a split message, `None` if it was stored in a single chunk.
+    pub message_id: Option<u64>,
+    pub data: Vec<u8>,
+    /// How many chunks the message was stored in.
+    pub parts: u32,
+    /// Whether the parts appeared in the file out of order.
+    pub reordered: bool,
+}
+
+impl Part {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = envelope::wrap(PART_FLAGS, &[]);
+        bytes.reserve(HEADER_LENGTH - bytes.len() + self.data.len());
+        bytes.extend_from_slice(&self.message_id.to_be_bytes());
+        bytes.extend_from_slice(&self.index.to_be_bytes());
+        bytes.extend_from_slice(&self.total.to_be_bytes());
+        bytes.extend_from_slice(&self.digest);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Part {
+    type Error = SplitError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let rest = match envelope::unwrap(bytes) {
+            Ok((flags, rest)) if flags.part => rest,
+            _ => return Err(SplitError::NotSplit),
+        };
+        let (message_id, rest) = rest.split_first_chunk::<8>().ok_or(SplitError::Truncated)?;
+        let (index, rest) = rest.split_first_chunk::<4>().ok_or(SplitError::Truncated)?;
+        let (total, rest) = rest.split_first_chunk::<4>().ok_or(SplitError::Truncated)?;
+        let (digest, data) = rest.split_first_chunk::<32>().ok_or(SplitError::Truncated)?;
+
+        let index = u32::from_be_bytes(*index);
+        let total = u32::from_be_bytes(*total);
+        if index >= total {
+            return Err(SplitError::InvalidIndex { index, total });
+        }
+
+        Ok(Part {
+            message_id: u64::from_be_bytes(*message_id),
+            index,
+            total,
+            digest: *digest,
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Whether `data` has an envelope that marks it as part of a split message.
+pub fn is_part(data: &[u8]) -> bool {
+    envelope::unwrap(data).is_ok_and(|(flags, _)| flags.part)
+}
+
+/// Splits `payload` into parts whose encoded size, header included, is at
+/// most `max_part_size` bytes. The parts share a freshly generated message ID.
+pub fn split(payload: &[u8], max_part_size: usize) -> Result<Vec<Part>, SplitError> {
+    let mut message_id = [0; 8];
+    getrandom::getrandom(&mut message_id).map_err(|_| SplitError::Random)?;
+    split_with_id(payload, u64::from_be_bytes(message_id), max_part_size)
+}
+
+fn split_with_id(payload: &[u8], message_id: u64, max_part_size: usize) -> Result<Vec<Part>, SplitError> {
+    if max_part_size <= HEADER_LENGTH {
+        return Err(SplitError::PartSizeTooSmall { size: max_part_size, minimum: HEADER_LENGTH + 1 });
+    }
+
+    let pieces: Vec<&[u8]> = match payload.is_empty() {
+        true => vec![payload],
+        false => payload.chunks(max_part_size - HEADER_LENGTH).collect(),
+    };
+    let total = u32::try_from(pieces.len()).map_err(|_| SplitError::TooManyParts(pieces.len()))?;
+    let digest: [u8; 32] = Sha256::digest(payload).into();
+
+    Ok(pieces
+        .into_iter()
+        .zip(0..)
+        .map(|(data, index)| Part { message_id, index, total, digest, data: data.to_vec() })
+        .collect())
+}
+
+/// Builds the chunks that store `payload` under `chunk_type`. The payload is
+/// only split when `max_part_size` is given and it doesn't fit in one chunk.
+pub fn to_chunks(chunk_type: &ChunkType, payload: Vec<u8>, max_part_size: Option<usize>) -> Result<Vec<Chunk>, SplitError> {
+    match max_part_size {
+        Some(max_part_size) if payload.len() > max_part_size => Ok(split(&payload, max_part_size)?
+            .iter()
+            .map(|part| Chunk::new(chunk_type.clone(), part.to_bytes()))
+            .collect()),
+        _ => Ok(vec![Chunk::new(chunk_type.clone(), payload)]),
+    }
+}
+
+/// Adds `payload` to the end of `png`, split as described in [`to_chunks`].
+pub fn append_message(png: &mut Png, chunk_type: &ChunkType, payload: Vec<u8>, max_part_size: Option<usize>) -> crate::Result<()> {
+    for chunk in to_chunks(chunk_type, payload, max_part_size)? {
+        png.append_chunk(chunk)?;
+    }
+    Ok(())
+}
+
+/// Reads every message stored under `chunk_type`, see [`reassemble`].
+pub fn read_messages(png: &Png, chunk_type: &ChunkType) -> Vec<Result<Message, SplitError>> {
+    reassemble(png.chunks_by_type(chunk_type).map(Chunk::data))
+}
+
+/// Turns the data of a run of chunks back into messages.
+///
+/// Chunks whose envelope doesn't mark them as a part are messages of their
+/// own. Parts are grouped by message ID and joined in index order, and each
+/// message takes the place of its first part. A part that doesn't parse, or
+/// a message with missing, duplicated or inconsistent parts or whose digest
+/// doesn't match, is an error in its place without affecting the others.
+pub fn reassemble<'a, I>(chunks: I) -> Vec<Result<Message, SplitError>>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    enum Slot {
+        Whole(Vec<u8>),
+        Split(u64),
+        Invalid(SplitError),
+    }
+
+    let mut slots = Vec::new();
+    let mut parts: HashMap<u64, Vec<Part>> = HashMap::new();
+
+    for data in chunks {
+        if !is_part(data) {
+            slots.push(Slot::Whole(data.to_vec()));
+            continue;
+        }
+
+        match Part::try_from(data) {
+            Ok(part) => {
+                let group = parts.entry(part.message_id).or_default();
+                if group.is_empty() {
+                    slots.push(Slot::Split(part.message_id));
+                }
+                group.push(part);
+            }
+            Err(err) => slots.push(Slot::Invalid(err)),
+        }
+    }
+
+    slots
+        .into_iter()
+        .map(|slot| match slot {
+            Slot::Whole(data) => Ok(Message { message_id: None, data, parts: 1, reordered: false }),
+            Slot::Split(message_id) => join(message_id, parts.remove(&message_id).unwrap_or_default()),
+            Slot::Invalid(err) => Err(err),
+        })
+        .collect()
+}
+
+fn join(message_id: u64, mut parts: Vec<Part>) -> Result<Message, SplitError> {
+    let (total, digest) = match parts.first() {
+        Some(first) => (first.total, first.digest),
+        None => return Err(SplitError::MissingParts { message_id, found: 0, total: 0 }),
+    };
+    if parts.iter().any(|part| part.total != total || part.digest != digest) {
+        return Err(SplitError::Inconsistent(message_id));
+    }
+
+    let reordered = parts.windows(2).any(|pair| pair[0].index > pair[1].index);
+    parts.sort_by_key(|part| part.index);
+
+    if let Some(pair) = parts.windows(2).find(|pair| pair[0].index == pair[1].index) {
+        return Err(SplitError::DuplicatePart { message_id, index: pair[0].index });
+    }
+    if parts.len() != total as usize {
+        return Err(SplitError::MissingParts { message_id, found: parts.len(), total });
+    }
+
+    let data: Vec<u8> = parts.into_iter().flat_map(|part| part.data).collect();
+    if Sha256::digest(&data).as_slice() != digest {
+        return Err(SplitError::DigestMismatch(message_id));
+    }
+
+    Ok(Message { message_id: Some(message_id), data, parts: total, reordered })
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SplitError {
+    NotSplit,
+    Truncated,
+    InvalidIndex { index: u32, total: u32 },
+    PartSizeTooSmall { size: usize, minimum: usize },
+    TooManyParts(usize),
+    MissingParts { message_id: u64, found: usize, total: u32 },
+    DuplicatePart { message_id: u64, index: u32 },
+    /// Parts with the same message ID disagree on the part count or digest.
+    Inconsistent(u64),
+    DigestMismatch(u64),
+    Random,
+}
+
+impl std::error::Error for SplitError {}
+
+impl Display for SplitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitError::NotSplit => write!(f, "The chunk does not hold part of a split message"),
+            SplitError::Truncated => write!(f, "The message part header is truncated"),
+            SplitError::InvalidIndex { index, total } => {
+                write!(f, "Message part {} is out of range for a message of {} parts", index, total)
+            }
+            SplitError::PartSizeTooSmall { size, minimum } => write!(
+                f,
+                "A maximum chunk size of {} bytes is too small to split a message, it must be at least {}",
+                size, minimum
+            ),
+            SplitError::TooManyParts(count) => write!(f, "The message would need {} parts, too many to number", count),
+            SplitError::MissingParts { message_id, found, total } => write!(
+                f,
+                "Message {:016x} is incomplete, found {} of {} parts",
+                message_id, found, total
+            ),
+            SplitError::DuplicatePart { message_id, index } => {
+                write!(f, "Message {:016x} has more than one part {}", message_id, index)
+            }
+            SplitError::Inconsistent(message_id) => {
+                write!(f, "The parts of message {:016x} do not belong to the same message", message_id)
+            }
+            SplitError::DigestMismatch(message_id) => {
+                write!(f, "Message {:016x} does not match its digest after reassembly", message_id)
+            }
+            SplitError::Random => write!(f, "Could not get random bytes from the operating system"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_parts() -> Vec<Vec<u8>> {
+        let payload: Vec<u8> = (0..=255).collect();
+        split_with_id(&payload, 7, HEADER_LENGTH + 100)
+            .unwrap()
+            .iter()
+            .map(Part::to_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn test_split_sizes() {
+        let parts = testing_parts();
+
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|part| part.len() <= HEADER_LENGTH + 100));
+        assert_eq!(parts[2].len(), HEADER_LENGTH + 56);
+    }
+
+    #[test]
+    fn test_part_round_trip() {
+        let bytes = testing_parts().remove(1);
+        let part = Part::try_from(bytes.as_slice()).unwrap();
+
+        assert_eq!(part.message_id, 7);
+        assert_eq!((part.index, part.total), (1, 3));
+        assert_eq!(part.to_bytes(), bytes);
+    }
+
+    fn reassemble_all<'a, I: IntoIterator<Item = &'a [u8]>>(chunks: I) -> Vec<Message> {
+        reassemble(chunks).into_iter().collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let parts = testing_parts();
+        let messages = reassemble_all(parts.iter().map(Vec::as_slice));
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].data, (0..=255).collect::<Vec<u8>>());
+        assert_eq!(messages[0].parts, 3);
+        assert!(!messages[0].reordered);
+    }
+
+    #[test]
+    fn test_reassemble_reordered() {
+        let parts = testing_parts();
+        let messages = reassemble_all([&parts[2], &parts[0], &parts[1]].map(Vec::as_slice));
+
+        assert_eq!(messages[0].data, (0..=255).collect::<Vec<u8>>());
+        assert!(messages[0].reordered);
+    }
+
+    #[test]
+    fn test_reassemble_missing_part() {
+        let parts = testing_parts();
+        let result = reassemble([&parts[0], &parts[2]].map(Vec::as_slice));
+        assert_eq!(result, [Err(SplitError::MissingParts { message_id: 7, found: 2, total: 3 })]);
+    }
+
+    #[test]
+    fn test_reassemble_duplicate_part() {
+        let parts = testing_parts();
+        let result = reassemble([&parts[0], &parts[1], &parts[1]].map(Vec::as_slice));
+        assert_eq!(result, [Err(SplitError::DuplicatePart { message_id: 7, index: 1 })]);
+    }
+
+    #[test]
+    fn test_reassemble_corrupted_part() {
+        let mut parts = testing_parts();
+        *parts[1].last_mut().unwrap() ^= 1;
+
+        let result = reassemble(parts.iter().map(Vec::as_slice));
+        assert_eq!(result, [Err(SplitError::DigestMismatch(7))]);
+    }
+
+    #[test]
+    fn test_reassemble_keeps_whole_messages_in_order() {
+        let parts = testing_parts();
+        let chunks: [&[u8]; 5] = [b"first", &parts[0], &parts[1], b"second", &parts[2]];
+        let messages = reassemble_all(chunks);
+
+        let data: Vec<&[u8]> = messages.iter().map(|message| message.data.as_slice()).collect();
+        assert_eq!(data, [b"first".as_slice(), &(0..=255).collect::<Vec<u8>>(), b"second"]);
+        assert_eq!(messages[0].message_id, None);
+        assert_eq!(messages[1].message_id, Some(7));
+    }
+
+    #[test]
+    fn test_plain_message_with_part_like_prefix() {
+        let messages = reassemble_all([b"pmp1".as_slice()]);
+        assert_eq!(messages[0].data, b"pmp1");
+        assert_eq!(messages[0].message_id, None);
+    }
+
+    #[test]
+    fn test_bad_part_only_affects_its_message() {
+        let parts = testing_parts();
+        let truncated = envelope::wrap(PART_FLAGS, b"\x00");
+        let chunks: [&[u8]; 5] = [&truncated, b"whole", &parts[0], &parts[1], &parts[2]];
+
+        let result = reassemble(chunks);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0], Err(SplitError::Truncated));
+        assert_eq!(result[1].as_ref().unwrap().data, b"whole");
+        assert_eq!(result[2].as_ref().unwrap().data, (0..=255).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_invalid_parts() {
+        assert_eq!(split(b"data", HEADER_LENGTH), Err(SplitError::PartSizeTooSmall { size: HEADER_LENGTH, minimum: HEADER_LENGTH + 1 }));
+        assert_eq!(Part::try_from(envelope::wrap(PART_FLAGS, b"\x00").as_slice()), Err(SplitError::Truncated));
+        assert_eq!(Part::try_from(envelope::wrap(Flags::default(), &[0; 64]).as_slice()), Err(SplitError::NotSplit));
+
+        let mut bytes = testing_parts().remove(0);
+        let index = envelope::HEADER_LENGTH + 8;
+        bytes[index..index + 4].copy_from_slice(&3u32.to_be_bytes());
+        assert_eq!(Part::try_from(bytes.as_slice()), Err(SplitError::InvalidIndex { index: 3, total: 3 }));
+    }
+
+    #[test]
+    fn test_append_and_read_messages() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![]),
+            Chunk::new(ChunkType::IDAT, vec![]),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ]);
+
+        append_message(&mut png, &chunk_type, b"short".to_vec(), Some(64)).unwrap();
+        append_message(&mut png, &chunk_type, vec![42; 1000], Some(64)).unwrap();
+
+        assert_eq!(png.chunks_by_type(&chunk_type).count(), 1 + 1000usize.div_ceil(64 - HEADER_LENGTH));
+        assert_eq!(*png.chunks().last().unwrap().chunk_type(), ChunkType::IEND);
+
+        let messages: Vec<Message> = read_messages(&png, &chunk_type).into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].data, b"short");
+        assert_eq!(messages[1].data, vec![42; 1000]);
+    }
+}
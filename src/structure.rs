@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use crate::chunk_type::ChunkType;
+use crate::registry::{self, Placement};
+
+/// Checks the chunk ordering rules from the PNG spec: IHDR comes first, at
+/// most one PLTE appears before the image data, the IDAT chunks are
+/// consecutive, and IEND comes last. Registered ancillary chunks must sit
+/// where their [`Placement`] says and appear at most once unless the spec
+/// allows several.
+pub fn validate<'a, I>(chunk_types: I) -> Result<(), StructureError>
+where
+    I: IntoIterator<Item = &'a ChunkType>,
+{
+    match violations(chunk_types).into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Every way the chunk order breaks the rules [`validate`] checks, in file
+/// order.
+pub fn violations<'a, I>(chunk_types: I) -> Vec<StructureError>
+where
+    I: IntoIterator<Item = &'a ChunkType>,
+{
+    let chunk_types: Vec<&ChunkType> = chunk_types.into_iter().collect();
+    let first_plte = chunk_types.iter().position(|&chunk_type| *chunk_type == ChunkType::PLTE);
+
+    let mut violations = Vec::new();
+    let mut seen = HashSet::new();
+    let mut seen_iend = false;
+    let mut idat = IdatState::NotSeen;
+
+    match chunk_types.first() {
+        None => return vec![StructureError::MissingChunk(ChunkType::IHDR)],
+        Some(&chunk_type) if *chunk_type != ChunkType::IHDR => {
+            violations.push(StructureError::FirstChunkNotIhdr(chunk_type.clone()));
+        }
+        Some(&chunk_type) => {
+            seen.insert(chunk_type);
+        }
+    }
+
+    for (index, &chunk_type) in chunk_types.iter().enumerate().skip(1) {
+        if seen_iend {
+            violations.push(StructureError::ChunkAfterIend { chunk_type: chunk_type.clone(), index });
+            continue;
+        }
+
+        if *chunk_type == ChunkType::IDAT {
+            if idat == IdatState::Ended {
+                violations.push(StructureError::IdatNotConsecutive { index });
+            }
+            idat = IdatState::InRun;
+            continue;
+        }
+
+        if idat == IdatState::InRun {
+            idat = IdatState::Ended;
+        }
+
+        let info = registry::lookup(chunk_type);
+        let single = *chunk_type == ChunkType::IHDR || info.is_some_and(|info| !info.multiple);
+        if !seen.insert(chunk_type) && single {
+            violations.push(StructureError::DuplicateChunk { chunk_type: chunk_type.clone(), index });
+        }
+
+        if *chunk_type == ChunkType::PLTE && idat != IdatState::NotSeen {
+            violations.push(StructureError::PlteAfterIdat { index });
+        }
+
+        if let Some(info) = info.filter(|_| !chunk_type.is_critical()) {
+            let after_plte = first_plte.is_some_and(|plte| index > plte);
+            let after_idat = idat != IdatState::NotSeen;
+            let misplaced = match info.placement {
+                Placement::BeforePlte => after_plte || after_idat,
+                Placement::AfterPlte => first_plte.is_some_and(|plte| index < plte) || after_idat,
+                Placement::BeforeIdat => after_idat,
+                Placement::AfterIdat => !after_idat,
+                Placement::First | Placement::Last | Placement::Consecutive | Placement::Anywhere => false,
+            };
+            if misplaced {
+                violations.push(StructureError::Misplaced { chunk_type: chunk_type.clone(), index, placement: info.placement });
+            }
+        }
+
+        if *chunk_type == ChunkType::IEND {
+            seen_iend = true;
+        }
+    }
+
+    if idat == IdatState::NotSeen {
+        violations.push(StructureError::MissingChunk(ChunkType::IDAT));
+    }
+    if !seen_iend {
+        violations.push(StructureError::MissingChunk(ChunkType::IEND));
+    }
+    violations
+}
+
+/// Checks putting a chunk of type `chunk_type` at `index`, either in place
+/// of the chunk there or before it. Only problems the change would cause are
+/// reported, so a file that already breaks a rule elsewhere can still be
+/// edited.
+pub fn check_change(
+    chunk_types: &[&ChunkType],
+    index: usize,
+    chunk_type: &ChunkType,
+    replace: bool,
+) -> Result<(), StructureError> {
+    let shift = if replace { 0 } else { 1 };
+    let existing: Vec<StructureError> = violations(chunk_types.iter().copied())
+        .into_iter()
+        .map(|err| err.shifted(index, shift))
+        .collect();
+
+    let mut changed = chunk_types.to_vec();
+    if replace {
+        changed[index] = chunk_type;
+    } else {
+        changed.insert(index, chunk_type);
+    }
+
+    match violations(changed).into_iter().find(|err| !existing.contains(err)) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Checks removing the chunks whose entry in `removed` is true. Like
+/// [`check_change`], only problems the removal would cause are reported.
+pub fn check_removal(chunk_types: &[&ChunkType], removed: &[bool]) -> Result<(), StructureError> {
+    // where each kept chunk ends up once the removed ones are gone
+    let mut new_index = Vec::with_capacity(chunk_types.len());
+    let mut next = 0;
+    for &removed in removed {
+        new_index.push((!removed).then_some(next));
+        next += usize::from(!removed);
+    }
+
+    let existing: Vec<StructureError> = violations(chunk_types.iter().copied())
+        .into_iter()
+        .filter_map(|err| err.moved(|index| new_index[index]))
+        .collect();
+
+    let kept = chunk_types.iter().zip(removed).filter(|(_, &removed)| !removed).map(|(&chunk_type, _)| chunk_type);
+    match violations(kept).into_iter().find(|err| !existing.contains(err)) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum IdatState {
+    NotSeen,
+    InRun,
+    Ended,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StructureError {
+    MissingChunk(ChunkType),
+    FirstChunkNotIhdr(ChunkType),
+    DuplicateChunk { chunk_type: ChunkType, index: usize },
+    ChunkAfterIend { chunk_type: ChunkType, index: usize },
+    PlteAfterIdat { index: usize },
+    IdatNotConsecutive { index: usize },
+    /// A chunk would separate an APNG frame's fcTL from its data, or split
+    /// the frame's fdAT chunks.
+    SplitsFrame { index: usize },
+    /// A registered ancillary chunk is somewhere the spec doesn't allow.
+    Misplaced { chunk_type: ChunkType, index: usize, placement: Placement },
+}
+
+impl StructureError {
+    /// The same error with chunk indexes from `from` on moved by `by`, as
+    /// they would be after inserting `by` chunks at `from`.
+    fn shifted(self, from: usize, by: usize) -> StructureError {
+        let shifted = self.moved(|index| Some(if index >= from { index + by } else { index }));
+        shifted.expect("shifting keeps every chunk")
+    }
+
+    /// The same error with its chunk index mapped through `new_index`, or
+    /// `None` when the chunk it is about is gone.
+    fn moved<F: Fn(usize) -> Option<usize>>(self, new_index: F) -> Option<StructureError> {
+        Some(match self {
+            StructureError::DuplicateChunk { chunk_type, index } => {
+                StructureError::DuplicateChunk { chunk_type, index: new_index(index)? }
+            }
+            StructureError::ChunkAfterIend { chunk_type, index } => {
+                StructureError::ChunkAfterIend { chunk_type, index: new_index(index)? }
+            }
+            StructureError::PlteAfterIdat { index } => StructureError::PlteAfterIdat { index: new_index(index)? },
+            StructureError::IdatNotConsecutive { index } => StructureError::IdatNotConsecutive { index: new_index(index)? },
+            StructureError::SplitsFrame { index } => StructureError::SplitsFrame { index: new_index(index)? },
+            StructureError::Misplaced { chunk_type, index, placement } => {
+                StructureError::Misplaced { chunk_type, index: new_index(index)?, placement }
+            }
+            other => other,
+        })
+    }
+}
+
+impl std::error::Error for StructureError {}
+
+impl Display for StructureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StructureError::MissingChunk(chunk_type) => write!(f, "Missing required {} chunk", chunk_type),
+            StructureError::FirstChunkNotIhdr(chunk_type) => {
+                write!(f, "The first chunk must be IHDR but found {}", chunk_type)
+            }
+            StructureError::DuplicateChunk { chunk_type, index } => {
+                write!(f, "Chunk #{} is a second {} chunk", index, chunk_type)
+            }
+            StructureError::ChunkAfterIend { chunk_type, index } => {
+                write!(f, "Chunk #{} ({}) comes after IEND", index, chunk_type)
+            }
+            StructureError::PlteAfterIdat { index } => {
+                write!(f, "Chunk #{} is a PLTE chunk after the image data", index)
+            }
+            StructureError::IdatNotConsecutive { index } => {
+                write!(f, "Chunk #{} is an IDAT chunk separated from the previous IDAT chunks", index)
+            }
+            StructureError::SplitsFrame { index } => {
+                write!(f, "A chunk at #{} would split an animation frame", index)
+            }
+            StructureError::Misplaced { chunk_type, index, placement } => {
+                write!(f, "Chunk #{} ({}) must come {}", index, chunk_type, placement)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk_types(types: &[&str]) -> Vec<ChunkType> {
+        types.iter().map(|t| ChunkType::from_str(t).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_valid_structure() {
+        let types = chunk_types(&["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "IDAT", "tEXt", "IEND"]);
+        assert_eq!(validate(&types), Ok(()));
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(validate(&[]), Err(StructureError::MissingChunk(ChunkType::IHDR)));
+    }
+
+    #[test]
+    fn test_ihdr_not_first() {
+        let types = chunk_types(&["gAMA", "IHDR", "IDAT", "IEND"]);
+        assert_eq!(validate(&types), Err(StructureError::FirstChunkNotIhdr(types[0].clone())));
+    }
+
+    #[test]
+    fn test_chunk_after_iend() {
+        let types = chunk_types(&["IHDR", "IDAT", "IEND", "ruSt"]);
+        assert_eq!(
+            validate(&types),
+            Err(StructureError::ChunkAfterIend { chunk_type: types[3].clone(), index: 3 })
+        );
+    }
+
+    #[test]
+    fn test_plte_after_idat() {
+        let types = chunk_types(&["IHDR", "IDAT", "PLTE", "IEND"]);
+        assert_eq!(validate(&types), Err(StructureError::PlteAfterIdat { index: 2 }));
+    }
+
+    #[test]
+    fn test_duplicate_plte() {
+        let types = chunk_types(&["IHDR", "PLTE", "PLTE", "IDAT", "IEND"]);
+        assert_eq!(
+            validate(&types),
+            Err(StructureError::DuplicateChunk { chunk_type: ChunkType::PLTE, index: 2 })
+        );
+    }
+
+    #[test]
+    fn test_idat_not_consecutive() {
+        let types = chunk_types(&["IHDR", "IDAT", "ruSt", "IDAT", "IEND"]);
+        assert_eq!(validate(&types), Err(StructureError::IdatNotConsecutive { index: 3 }));
+    }
+
+    #[test]
+    fn test_missing_idat_and_iend() {
+        let types = chunk_types(&["IHDR", "IEND"]);
+        assert_eq!(validate(&types), Err(StructureError::MissingChunk(ChunkType::IDAT)));
+
+        let types = chunk_types(&["IHDR", "IDAT"]);
+        assert_eq!(validate(&types), Err(StructureError::MissingChunk(ChunkType::IEND)));
+    }
+
+    #[test]
+    fn test_misplaced_ancillary_chunks() {
+        let types = chunk_types(&["IHDR", "PLTE", "gAMA", "IDAT", "IEND"]);
+        assert_eq!(
+            validate(&types),
+            Err(StructureError::Misplaced { chunk_type: types[2].clone(), index: 2, placement: Placement::BeforePlte })
+        );
+
+        let types = chunk_types(&["IHDR", "tRNS", "PLTE", "IDAT", "IEND"]);
+        assert!(matches!(validate(&types), Err(StructureError::Misplaced { index: 1, .. })));
+
+        let types = chunk_types(&["IHDR", "IDAT", "pHYs", "IEND"]);
+        assert!(matches!(validate(&types), Err(StructureError::Misplaced { index: 2, .. })));
+    }
+
+    #[test]
+    fn test_duplicate_ancillary_chunk() {
+        let types = chunk_types(&["IHDR", "gAMA", "gAMA", "IDAT", "tEXt", "tEXt", "IEND"]);
+        assert_eq!(validate(&types), Err(StructureError::DuplicateChunk { chunk_type: types[1].clone(), index: 2 }));
+    }
+
+    #[test]
+    fn test_violations_lists_every_problem() {
+        let types = chunk_types(&["IHDR", "IDAT", "gAMA", "IDAT", "IEND", "ruSt"]);
+        assert_eq!(violations(&types).len(), 3);
+    }
+
+    #[test]
+    fn test_check_change_ignores_existing_problems() {
+        let types = chunk_types(&["IHDR", "IDAT", "ruSt", "IDAT", "IEND"]);
+        let types: Vec<&ChunkType> = types.iter().collect();
+        let new_type = ChunkType::from_str("neWt").unwrap();
+
+        assert_eq!(check_change(&types, 1, &new_type, false), Ok(()));
+        assert_eq!(check_change(&types, 2, &new_type, true), Ok(()));
+        assert_eq!(
+            check_change(&types, 5, &new_type, false),
+            Err(StructureError::ChunkAfterIend { chunk_type: new_type.clone(), index: 5 })
+        );
+        assert!(matches!(
+            check_change(&types, 4, &ChunkType::from_str("gAMA").unwrap(), false),
+            Err(StructureError::Misplaced { index: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_removal() {
+        let types = chunk_types(&["IHDR", "IDAT", "ruSt", "IDAT", "tEXt", "IEND"]);
+        let types: Vec<&ChunkType> = types.iter().collect();
+
+        assert_eq!(check_removal(&types, &[false, false, false, false, true, false]), Ok(()));
+        assert_eq!(check_removal(&types, &[false, false, true, false, false, false]), Ok(()));
+        assert_eq!(
+            check_removal(&types, &[true, false, false, false, false, false]),
+            Err(StructureError::FirstChunkNotIhdr(ChunkType::IDAT))
+        );
+        assert_eq!(
+            check_removal(&types, &[false, true, false, true, false, false]),
+            Err(StructureError::MissingChunk(ChunkType::IDAT))
+        );
+        assert_eq!(
+            check_removal(&types, &[false, false, false, false, false, true]),
+            Err(StructureError::MissingChunk(ChunkType::IEND))
+        );
+    }
+}
@@ -0,0 +1,487 @@
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::compression::DEFAULT_SIZE_LIMIT;
+
+pub const MAX_KEYWORD_LENGTH: usize = 79;
+/// The only compression method the spec defines for zTXt and iTXt, zlib.
+pub const COMPRESSION_METHOD_DEFLATE: u8 = 0;
+
+/// A validated text chunk keyword: 1 to 79 printable Latin-1 characters
+/// without leading, trailing or consecutive spaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyword(String);
+
+/// Uncompressed Latin-1 text, stored in a tEXt chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Text {
+    pub keyword: Keyword,
+    text: String,
+}
+
+/// Deflated Latin-1 text, stored in a zTXt chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedText {
+    pub keyword: Keyword,
+    text: String,
+}
+
+/// UTF-8 text with an optional language tag and translated keyword, stored
+/// in an iTXt chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalText {
+    pub keyword: Keyword,
+    pub compressed: bool,
+    language_tag: String,
+    translated_keyword: String,
+    text: String,
+}
+
+/// Any of the three textual chunk types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextualChunk {
+    Text(Text),
+    Compressed(CompressedText),
+    International(InternationalText),
+}
+
+impl Keyword {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn to_latin1(&self) -> Vec<u8> {
+        self.0.chars().map(|c| c as u8).collect()
+    }
+}
+
+impl FromStr for Keyword {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(TextError::EmptyKeyword);
+        }
+        // every allowed character is a single Latin-1 byte
+        let length = s.chars().count();
+        if length > MAX_KEYWORD_LENGTH {
+            return Err(TextError::KeywordTooLong(length));
+        }
+        if let Some(c) = s.chars().find(|&c| !matches!(c as u32, 32..=126 | 161..=255)) {
+            return Err(TextError::InvalidKeywordCharacter(c));
+        }
+        if s.starts_with(' ') || s.ends_with(' ') || s.contains("  ") {
+            return Err(TextError::KeywordSpaces);
+        }
+        Ok(Keyword(s.to_string()))
+    }
+}
+
+impl TryFrom<&[u8]> for Keyword {
+    type Error = TextError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Keyword::from_str(&from_latin1(bytes))
+    }
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Text {
+    pub fn new(keyword: Keyword, text: &str) -> Result<Self, TextError> {
+        to_latin1(text)?;
+        Ok(Text { keyword, text: text.to_string() })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.keyword.to_latin1();
+        data.push(0);
+        data.extend(self.text.chars().map(|c| c as u8));
+        Chunk::new(ChunkType::TEXT, data)
+    }
+}
+
+impl TryFrom<&[u8]> for Text {
+    type Error = TextError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (keyword, text) = split_null(data)?;
+        Ok(Text { keyword: Keyword::try_from(keyword)?, text: from_latin1(text) })
+    }
+}
+
+impl CompressedText {
+    pub fn new(keyword: Keyword, text: &str) -> Result<Self, TextError> {
+        to_latin1(text)?;
+        Ok(CompressedText { keyword, text: text.to_string() })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.keyword.to_latin1();
+        data.push(0);
+        data.push(COMPRESSION_METHOD_DEFLATE);
+        data.extend(deflate(&self.text.chars().map(|c| c as u8).collect::<Vec<u8>>()));
+        Chunk::new(ChunkType::ZTXT, data)
+    }
+}
+
+impl TryFrom<&[u8]> for CompressedText {
+    type Error = TextError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (keyword, rest) = split_null(data)?;
+        let (&method, compressed) = rest.split_first().ok_or(TextError::Truncated)?;
+        if method != COMPRESSION_METHOD_DEFLATE {
+            return Err(TextError::UnknownCompressionMethod(method));
+        }
+
+        Ok(CompressedText { keyword: Keyword::try_from(keyword)?, text: from_latin1(&inflate(compressed)?) })
+    }
+}
+
+impl InternationalText {
+    pub fn new(keyword: Keyword, text: &str) -> Result<Self, TextError> {
+        if text.contains('\0') {
+            return Err(TextError::NullCharacter);
+        }
+        Ok(InternationalText {
+            keyword,
+            compressed: false,
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+            text: text.to_string(),
+        })
+    }
+
+    /// Sets the language of the text and translated keyword, an RFC 3066 tag
+    /// such as "en" or "pt-BR"; empty means unknown.
+    pub fn with_language_tag(mut self, language_tag: &str) -> Result<Self, TextError> {
+        let valid = language_tag.is_empty()
+            || language_tag
+                .split('-')
+                .all(|part| (1..=8).contains(&part.len()) && part.bytes().all(|b| b.is_ascii_alphanumeric()));
+        if !valid {
+            return Err(TextError::InvalidLanguageTag(language_tag.to_string()));
+        }
+
+        self.language_tag = language_tag.to_string();
+        Ok(self)
+    }
+
+    pub fn with_translated_keyword(mut self, translated_keyword: &str) -> Result<Self, TextError> {
+        if translated_keyword.contains('\0') {
+            return Err(TextError::NullCharacter);
+        }
+
+        self.translated_keyword = translated_keyword.to_string();
+        Ok(self)
+    }
+
+    pub fn language_tag(&self) -> &str {
+        &self.language_tag
+    }
+
+    pub fn translated_keyword(&self) -> &str {
+        &self.translated_keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.keyword.to_latin1();
+        data.push(0);
+        data.push(self.compressed as u8);
+        data.push(COMPRESSION_METHOD_DEFLATE);
+        data.extend_from_slice(self.language_tag.as_bytes());
+        data.push(0);
+        data.extend_from_slice(self.translated_keyword.as_bytes());
+        data.push(0);
+        match self.compressed {
+            true => data.extend(deflate(self.text.as_bytes())),
+            false => data.extend_from_slice(self.text.as_bytes()),
+        }
+        Chunk::new(ChunkType::ITXT, data)
+    }
+}
+
+impl TryFrom<&[u8]> for InternationalText {
+    type Error = TextError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (keyword, rest) = split_null(data)?;
+        let (flags, rest) = rest.split_first_chunk::<2>().ok_or(TextError::Truncated)?;
+        let (language_tag, rest) = split_null(rest)?;
+        let (translated_keyword, text) = split_null(rest)?;
+
+        let compressed = match flags[0] {
+            0 => false,
+            1 => true,
+            flag => return Err(TextError::InvalidCompressionFlag(flag)),
+        };
+        if compressed && flags[1] != COMPRESSION_METHOD_DEFLATE {
+            return Err(TextError::UnknownCompressionMethod(flags[1]));
+        }
+        let text = match compressed {
+            true => String::from_utf8(inflate(text)?).map_err(|_| TextError::InvalidUtf8)?,
+            false => std::str::from_utf8(text).map_err(|_| TextError::InvalidUtf8)?.to_string(),
+        };
+
+        Ok(InternationalText {
+            keyword: Keyword::try_from(keyword)?,
+            compressed,
+            language_tag: std::str::from_utf8(language_tag).map_err(|_| TextError::InvalidUtf8)?.to_string(),
+            translated_keyword: std::str::from_utf8(translated_keyword).map_err(|_| TextError::InvalidUtf8)?.to_string(),
+            text,
+        })
+    }
+}
+
+impl TextualChunk {
+    pub fn keyword(&self) -> &Keyword {
+        match self {
+            TextualChunk::Text(text) => &text.keyword,
+            TextualChunk::Compressed(text) => &text.keyword,
+            TextualChunk::International(text) => &text.keyword,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            TextualChunk::Text(text) => text.text(),
+            TextualChunk::Compressed(text) => text.text(),
+            TextualChunk::International(text) => text.text(),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        match self {
+            TextualChunk::Text(text) => text.to_chunk(),
+            TextualChunk::Compressed(text) => text.to_chunk(),
+            TextualChunk::International(text) => text.to_chunk(),
+        }
+    }
+}
+
+impl TryFrom<&Chunk> for TextualChunk {
+    type Error = TextError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        match *chunk.chunk_type() {
+            ChunkType::TEXT => Ok(TextualChunk::Text(Text::try_from(chunk.data())?)),
+            ChunkType::ZTXT => Ok(TextualChunk::Compressed(CompressedText::try_from(chunk.data())?)),
+            ChunkType::ITXT => Ok(TextualChunk::International(InternationalText::try_from(chunk.data())?)),
+            ref other => Err(TextError::NotTextChunk(other.clone())),
+        }
+    }
+}
+
+/// Whether chunks of this type hold text: tEXt, zTXt or iTXt.
+pub fn is_text_chunk(chunk_type: &ChunkType) -> bool {
+    [ChunkType::TEXT, ChunkType::ZTXT, ChunkType::ITXT].contains(chunk_type)
+}
+
+fn split_null(data: &[u8]) -> Result<(&[u8], &[u8]), TextError> {
+    let end = data.iter().position(|&b| b == 0).ok_or(TextError::MissingSeparator)?;
+    Ok((&data[..end], &data[end + 1..]))
+}
+
+fn to_latin1(text: &str) -> Result<Vec<u8>, TextError> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0 => Err(TextError::NullCharacter),
+            1..=255 => Ok(c as u8),
+            _ => Err(TextError::NotLatin1(c)),
+        })
+        .collect()
+}
+
+fn from_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+fn inflate(compressed: &[u8]) -> Result<Vec<u8>, TextError> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take(DEFAULT_SIZE_LIMIT as u64 + 1)
+        .read_to_end(&mut inflated)
+        .map_err(TextError::Decompress)?;
+
+    if inflated.len() > DEFAULT_SIZE_LIMIT {
+        return Err(TextError::TooLarge(DEFAULT_SIZE_LIMIT));
+    }
+    Ok(inflated)
+}
+
+#[derive(Debug)]
+pub enum TextError {
+    EmptyKeyword,
+    KeywordTooLong(usize),
+    InvalidKeywordCharacter(char),
+    KeywordSpaces,
+    NotLatin1(char),
+    NullCharacter,
+    InvalidLanguageTag(String),
+    /// The null byte that ends the keyword or another field is missing.
+    MissingSeparator,
+    Truncated,
+    InvalidCompressionFlag(u8),
+    UnknownCompressionMethod(u8),
+    Decompress(std::io::Error),
+    TooLarge(usize),
+    InvalidUtf8,
+    NotTextChunk(ChunkType),
+}
+
+impl std::error::Error for TextError {}
+
+impl Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextError::EmptyKeyword => write!(f, "The keyword is empty"),
+            TextError::KeywordTooLong(length) => write!(
+                f,
+                "The keyword is {} characters long, at most {} are allowed",
+                length, MAX_KEYWORD_LENGTH
+            ),
+            TextError::InvalidKeywordCharacter(c) => {
+                write!(f, "The keyword contains {:?}, only printable Latin-1 characters are allowed", c)
+            }
+            TextError::KeywordSpaces => {
+                write!(f, "The keyword can't start or end with a space or contain consecutive spaces")
+            }
+            TextError::NotLatin1(c) => write!(f, "{:?} is not a Latin-1 character, use an iTXt chunk instead", c),
+            TextError::NullCharacter => write!(f, "Text chunks can't contain null characters"),
+            TextError::InvalidLanguageTag(tag) => write!(f, "{:?} is not a valid language tag", tag),
+            TextError::MissingSeparator => write!(f, "The text chunk is missing a null separator"),
+            TextError::Truncated => write!(f, "The text chunk is truncated"),
+            TextError::InvalidCompressionFlag(flag) => write!(f, "Invalid iTXt compression flag {}", flag),
+            TextError::UnknownCompressionMethod(method) => write!(f, "Unknown text compression method {}", method),
+            TextError::Decompress(err) => write!(f, "Failed to decompress the text: {}", err),
+            TextError::TooLarge(limit) => write!(f, "The text decompresses to more than {} bytes", limit),
+            TextError::InvalidUtf8 => write!(f, "The iTXt chunk is not valid UTF-8"),
+            TextError::NotTextChunk(chunk_type) => write!(f, "{} is not a text chunk type", chunk_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyword(s: &str) -> Keyword {
+        Keyword::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_keyword_validation() {
+        assert_eq!(keyword("Comment").as_str(), "Comment");
+        assert_eq!(keyword("Creation Time").as_str(), "Creation Time");
+        assert!(Keyword::from_str(&"a".repeat(MAX_KEYWORD_LENGTH)).is_ok());
+
+        assert!(matches!(Keyword::from_str(""), Err(TextError::EmptyKeyword)));
+        assert!(matches!(Keyword::from_str(&"a".repeat(80)), Err(TextError::KeywordTooLong(80))));
+        assert!(matches!(Keyword::from_str(" Comment"), Err(TextError::KeywordSpaces)));
+        assert!(matches!(Keyword::from_str("Comment "), Err(TextError::KeywordSpaces)));
+        assert!(matches!(Keyword::from_str("Two  Spaces"), Err(TextError::KeywordSpaces)));
+        assert!(matches!(Keyword::from_str("Tab\there"), Err(TextError::InvalidKeywordCharacter('\t'))));
+        assert!(matches!(Keyword::from_str("Snow☃"), Err(TextError::InvalidKeywordCharacter('☃'))));
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let text = Text::new(keyword("Comment"), "Café au lait").unwrap();
+        let chunk = text.to_chunk();
+
+        assert_eq!(*chunk.chunk_type(), ChunkType::TEXT);
+        assert_eq!(chunk.data(), b"Comment\0Caf\xe9 au lait");
+        assert_eq!(TextualChunk::try_from(&chunk).unwrap(), TextualChunk::Text(text));
+    }
+
+    #[test]
+    fn test_text_rejects_non_latin1() {
+        assert!(matches!(Text::new(keyword("Comment"), "日本"), Err(TextError::NotLatin1('日'))));
+        assert!(matches!(CompressedText::new(keyword("Comment"), "a\0b"), Err(TextError::NullCharacter)));
+    }
+
+    #[test]
+    fn test_compressed_text_round_trip() {
+        let text = CompressedText::new(keyword("Description"), &"all work and no play ".repeat(50)).unwrap();
+        let chunk = text.to_chunk();
+
+        assert_eq!(*chunk.chunk_type(), ChunkType::ZTXT);
+        assert!(chunk.length() < 100);
+        assert_eq!(TextualChunk::try_from(&chunk).unwrap(), TextualChunk::Compressed(text));
+    }
+
+    #[test]
+    fn test_international_text_round_trip() {
+        for compressed in [false, true] {
+            let mut text = InternationalText::new(keyword("Title"), "日本の夏")
+                .unwrap()
+                .with_language_tag("ja")
+                .unwrap()
+                .with_translated_keyword("タイトル")
+                .unwrap();
+            text.compressed = compressed;
+
+            let parsed = TextualChunk::try_from(&text.to_chunk()).unwrap();
+            assert_eq!(parsed.keyword().as_str(), "Title");
+            assert_eq!(parsed.text(), "日本の夏");
+            assert_eq!(parsed, TextualChunk::International(text));
+        }
+    }
+
+    #[test]
+    fn test_international_text_fields() {
+        let data = b"Title\0\0\0en-GB\0Title\0Colour";
+        let text = InternationalText::try_from(data.as_slice()).unwrap();
+
+        assert!(!text.compressed);
+        assert_eq!(text.language_tag(), "en-GB");
+        assert_eq!(text.translated_keyword(), "Title");
+        assert_eq!(text.text(), "Colour");
+    }
+
+    #[test]
+    fn test_invalid_language_tag() {
+        let text = InternationalText::new(keyword("Title"), "x").unwrap();
+        assert!(matches!(text.with_language_tag("en_GB"), Err(TextError::InvalidLanguageTag(_))));
+    }
+
+    #[test]
+    fn test_invalid_chunks() {
+        assert!(matches!(Text::try_from(b"no separator".as_slice()), Err(TextError::MissingSeparator)));
+        assert!(matches!(Text::try_from(b"\0text".as_slice()), Err(TextError::EmptyKeyword)));
+        assert!(matches!(CompressedText::try_from(b"Comment\0\x01x".as_slice()), Err(TextError::UnknownCompressionMethod(1))));
+        assert!(matches!(InternationalText::try_from(b"Title\0\x02\0\0\0".as_slice()), Err(TextError::InvalidCompressionFlag(2))));
+        assert!(matches!(InternationalText::try_from(b"Title\0\0\0\0\0\xff".as_slice()), Err(TextError::InvalidUtf8)));
+
+        let chunk = Chunk::new(ChunkType::IEND, vec![]);
+        assert!(matches!(TextualChunk::try_from(&chunk), Err(TextError::NotTextChunk(_))));
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::Write;
+use crate::chunk::Chunk;
+use crate::png::Png;
+use crate::Result;
+
+/// Writes a PNG to any `Write` sink one chunk at a time.
+///
+/// The signature is written by [`PngWriter::new`]. Chunks can then be written
+/// individually or copied from another source such as a
+/// [`PngReader`](crate::reader::PngReader), optionally splicing in new chunks
+/// along the way, without the whole image ever being held in memory.
+pub struct PngWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> PngWriter<W> {
+    pub fn new(mut inner: W) -> Result<PngWriter<W>> {
+        inner.write_all(&Png::STANDARD_HEADER)?;
+        Ok(PngWriter { inner })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        chunk.write_to(&mut self.inner)?;
+        Ok(())
+    }
+
+    /// Writes every chunk from `chunks` unchanged, stopping at the first error.
+    pub fn copy_chunks<I>(&mut self, chunks: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<Chunk>>,
+    {
+        for chunk in chunks {
+            self.write_chunk(&chunk?)?;
+        }
+        Ok(())
+    }
+
+    /// Copies `chunks`, writing whatever `splice` returns in place of each one.
+    ///
+    /// Returning `vec![chunk]` passes a chunk through, an empty `Vec` drops it,
+    /// and returning extra chunks around it inserts them at that position.
+    pub fn copy_chunks_with<I, F>(&mut self, chunks: I, mut splice: F) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<Chunk>>,
+        F: FnMut(Chunk) -> Vec<Chunk>,
+    {
+        for chunk in chunks {
+            for chunk in splice(chunk?) {
+                self.write_chunk(&chunk)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the sink and hands it back.
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::reader::PngReader;
+    use std::str::FromStr;
+
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk_from_strings("FrSt", "I am the first chunk"),
+            chunk_from_strings("miDl", "I am another chunk"),
+            chunk_from_strings("LASt", "I am the last chunk"),
+        ])
+    }
+
+    #[test]
+    fn test_writes_same_bytes_as_png() {
+        let png = testing_png();
+
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        for chunk in png.chunks() {
+            writer.write_chunk(chunk).unwrap();
+        }
+
+        assert_eq!(writer.finish().unwrap(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_copy_chunks_round_trips() {
+        let bytes = testing_png().as_bytes();
+        let reader = PngReader::new(bytes.as_slice()).unwrap();
+
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        writer.copy_chunks(reader).unwrap();
+
+        assert_eq!(writer.finish().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_copy_chunks_with_splice() {
+        let bytes = testing_png().as_bytes();
+        let reader = PngReader::new(bytes.as_slice()).unwrap();
+
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        writer
+            .copy_chunks_with(reader, |chunk| match chunk.chunk_type().to_string().as_str() {
+                "miDl" => vec![],
+                "LASt" => vec![chunk_from_strings("TeSt", "Message"), chunk],
+                _ => vec![chunk],
+            })
+            .unwrap();
+
+        let png = Png::try_from(writer.finish().unwrap().as_slice()).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+
+        assert_eq!(types, ["FrSt", "TeSt", "LASt"]);
+    }
+}